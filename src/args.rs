@@ -0,0 +1,214 @@
+use crate::boundary::Boundary;
+use crate::config::{Config, FillConfig, UpdateConfig};
+use crate::life::{FillRegion, UpdateMode};
+use crate::rule::Rule;
+use crate::{DEFAULT_CELL_SIZE, DEFAULT_COLS, DEFAULT_HEADLESS_GENERATIONS, DEFAULT_ROWS, MAX_SPEED, MIN_CELL_SIZE, MIN_SPEED};
+
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+Usage: conway [OPTIONS] [PATTERN]
+
+Arguments:
+  [PATTERN]            Plaintext (.cells) or RLE (.rle) pattern file to open at startup
+
+Options:
+  -r, --rule <RULE>    Rule to simulate (default: from config), one of:
+                         B/S notation, e.g. B36/S23, with Hensel letters as in B2-a/S12
+                         and a V, H or @ neighbourhood as in B1/S012V
+                         Generations, e.g. B2/S/C3
+                         Larger than Life, e.g. R5,C0,M1,S34..58,B34..45,NM
+                         a rule table by name or path, e.g. WireWorld or loops.rule
+                         one-dimensional, e.g. W30 or T52,R2
+                         an ant or turmite, e.g. RL or {{{1,2,0},{0,8,0}}}
+  -s, --size <RxC>     Size of the simulated world in rows and columns (default: 120x120)
+  -b, --boundary <B>   Boundary mode: void, wrap, klein, cross, sphere, hcylinder, vcylinder
+                       or reflect (default: from config)
+      --speed <GPS>    Generations per second, from 1 to 10000 (default: from config)
+  -z, --zoom <PX>      Size of each cell on screen in pixels (default: 30)
+      --track-window   Resize the world to fit the cells visible in the window
+      --run            Start the simulation immediately
+      --soup           Start with a random soup instead of an empty grid
+      --density <P>    Probability of each cell being alive in a soup, from 0 to 1
+      --fill <REGION>  Region filled by a soup: all, selection or box (default: from config)
+      --box <N>        Size of the centred box filled by a soup (default: from config)
+      --seed <N>       Seed for soups, making them reproducible (default: random)
+      --update <MODE>  How cells update: sync, stochastic or async (default: from config)
+      --probability <P>
+                       Probability of each cell changing under stochastic updates
+      --noise <P>      Probability of each cell flipping between dead and alive every
+                       generation (default: from config)
+      --update-seed <N>
+                       Seed for random updates and noise, making runs reproducible
+      --headless       Run without a window and report whether the pattern repeats
+      --generations <N>
+                       Most generations to run without a window (default: 1000)
+  -h, --help           Print this message";
+
+/// Options given on the command line
+pub struct Args {
+    pub pattern: Option<PathBuf>,
+    pub rule: Rule,
+    pub rows: usize,
+    pub cols: usize,
+    pub boundary: Boundary,
+    pub speed: u32,
+    pub cell_size: i32,
+    pub is_tracking_window: bool,
+    pub is_running: bool,
+    pub is_soup: bool,
+    pub fill: FillConfig,
+    pub update: UpdateConfig,
+    pub is_headless: bool,
+    pub generations: u64,
+}
+
+impl Args {
+    /// Get the options to use when none are given on the command line, from the user's config
+    pub fn from_config(config: &Config) -> Self {
+        let rule: Rule = match Rule::parse(&config.rule) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("WARNING: Ignoring rule in config: {}", e);
+                Rule::default()
+            },
+        };
+
+        let boundary: Boundary = Boundary::from_name(&config.boundary).unwrap_or_else(|| {
+            eprintln!("WARNING: Ignoring unknown boundary {} in config", config.boundary);
+            Boundary::Void
+        });
+
+        // an out of range density in the config would make every soup fail, so keep it in range
+        let mut fill: FillConfig = config.fill.clone();
+        fill.density = fill.density.clamp(0.0, 1.0);
+        let mut update: UpdateConfig = config.update.clone();
+        update.probability = update.probability.clamp(0.0, 1.0);
+        update.noise = update.noise.clamp(0.0, 1.0);
+
+        Args {
+            pattern: None,
+            rule,
+            rows: DEFAULT_ROWS,
+            cols: DEFAULT_COLS,
+            boundary,
+            speed: config.speed.clamp(MIN_SPEED, MAX_SPEED),
+            cell_size: DEFAULT_CELL_SIZE,
+            is_tracking_window: config.track_window,
+            is_running: false,
+            is_soup: false,
+            fill,
+            update,
+            is_headless: false,
+            generations: DEFAULT_HEADLESS_GENERATIONS,
+        }
+    }
+}
+
+/// Get the usage message for the command line
+pub fn usage() -> &'static str {
+    USAGE
+}
+
+/// Parse the command-line arguments, not including the program name, on top of the config
+/// Returns Ok(None) if the help message was requested
+pub fn parse_args(
+    mut args: impl Iterator<Item = String>,
+    config: &Config,
+) -> Result<Option<Args>, String> {
+    let mut parsed = Args::from_config(config);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--run" => parsed.is_running = true,
+            "--track-window" => parsed.is_tracking_window = true,
+            "--soup" => parsed.is_soup = true,
+            "--headless" => parsed.is_headless = true,
+            "--generations" => parsed.generations = parse_number(&next_value(&mut args, &arg)?)?,
+            "--density" => parsed.fill.density = parse_probability(&next_value(&mut args, &arg)?)?,
+            "--fill" => {
+                parsed.fill.region = match next_value(&mut args, &arg)?.to_lowercase().as_str() {
+                    "all" | "whole" => FillRegion::Whole,
+                    "selection" => FillRegion::Selection,
+                    "box" => FillRegion::Box,
+                    other => return Err(format!("Unknown fill region {}", other)),
+                };
+            },
+            "--box" => {
+                let box_size: usize = parse_number(&next_value(&mut args, &arg)?)?;
+                parsed.fill.box_size = box_size.max(1);
+            },
+            "--seed" => parsed.fill.seed = Some(parse_number(&next_value(&mut args, &arg)?)?),
+            "--update" => {
+                let name: String = next_value(&mut args, &arg)?;
+                parsed.update.mode = UpdateMode::from_name(&name)
+                    .ok_or(format!("Unknown update mode {}", name))?;
+            },
+            "--probability" => parsed.update.probability = parse_probability(&next_value(&mut args, &arg)?)?,
+            "--noise" => parsed.update.noise = parse_probability(&next_value(&mut args, &arg)?)?,
+            "--update-seed" => parsed.update.seed = Some(parse_number(&next_value(&mut args, &arg)?)?),
+            "-r" | "--rule" => {
+                parsed.rule = Rule::parse(&next_value(&mut args, &arg)?)?;
+            },
+            "-s" | "--size" => {
+                let (rows, cols) = parse_size(&next_value(&mut args, &arg)?)?;
+                parsed.rows = rows;
+                parsed.cols = cols;
+            },
+            "-b" | "--boundary" => {
+                let name: String = next_value(&mut args, &arg)?;
+                parsed.boundary = Boundary::from_name(&name)
+                    .ok_or(format!("Unknown boundary mode {}", name))?;
+            },
+            "--speed" => {
+                let speed: u32 = parse_number(&next_value(&mut args, &arg)?)?;
+                parsed.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+            },
+            "-z" | "--zoom" => {
+                let cell_size: i32 = parse_number(&next_value(&mut args, &arg)?)?;
+                parsed.cell_size = cell_size.max(MIN_CELL_SIZE);
+            },
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ => {
+                if parsed.pattern.is_some() {
+                    return Err("Only one pattern file can be given".to_string());
+                }
+                parsed.pattern = Some(PathBuf::from(arg));
+            },
+        }
+    }
+
+    Ok(Some(parsed))
+}
+
+/// Get the value following an option, or an error if there is none
+fn next_value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, String> {
+    args.next().ok_or(format!("Missing value for {}", option))
+}
+
+/// Parse a number given as an option value
+fn parse_number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.trim().parse().map_err(|_| format!("Invalid number {}", text))
+}
+
+/// Parse a probability given as an option value, which must be between 0 and 1
+fn parse_probability(text: &str) -> Result<f64, String> {
+    let probability: f64 = parse_number(text)?;
+    if !(0.0..=1.0).contains(&probability) {
+        return Err(format!("{} must be between 0 and 1", probability));
+    }
+    Ok(probability)
+}
+
+/// Parse a world size in the form ROWSxCOLS, where each dimension must fit a 3x3 neighbourhood
+pub fn parse_size(text: &str) -> Result<(usize, usize), String> {
+    let (rows, cols) = match text.to_lowercase().split_once('x') {
+        Some((rows, cols)) => (parse_number(rows)?, parse_number(cols)?),
+        None => return Err(format!("Invalid size {}, expected the form 120x120", text)),
+    };
+    if rows < 3 || cols < 3 {
+        return Err("World must be at least 3x3".to_string());
+    }
+    Ok((rows, cols))
+}
@@ -0,0 +1,255 @@
+use serde::{Deserialize, Serialize};
+
+/// How the edges of the world are treated, deciding which cells lie beyond each edge
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Boundary {
+    /// Everything beyond the edges is dead, and cells on the edges die
+    Void,
+    /// Opposite edges are joined, making a torus
+    Wrap,
+    /// Left and right edges are joined, and top and bottom are joined with a twist
+    Klein,
+    /// Both pairs of opposite edges are joined with a twist
+    Cross,
+    /// The top edge is joined to the left edge and the bottom edge to the right edge
+    Sphere,
+    /// Left and right edges are joined, and everything above and below is dead
+    #[serde(rename = "hcylinder")]
+    HorizontalCylinder,
+    /// Top and bottom edges are joined, and everything left and right is dead
+    #[serde(rename = "vcylinder")]
+    VerticalCylinder,
+    /// The edges act as mirrors, so cells beyond them are mirror images of the cells inside,
+    /// starting with the cells on the edges
+    Reflect,
+}
+
+/// Every boundary, in the order they are cycled through
+pub const BOUNDARIES: [Boundary; 8] = [
+    Boundary::Void,
+    Boundary::Wrap,
+    Boundary::Klein,
+    Boundary::Cross,
+    Boundary::Sphere,
+    Boundary::HorizontalCylinder,
+    Boundary::VerticalCylinder,
+    Boundary::Reflect,
+];
+
+impl Boundary {
+    /// Get the name of the boundary, as used in the config file and on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Boundary::Void => "void",
+            Boundary::Wrap => "wrap",
+            Boundary::Klein => "klein",
+            Boundary::Cross => "cross",
+            Boundary::Sphere => "sphere",
+            Boundary::HorizontalCylinder => "hcylinder",
+            Boundary::VerticalCylinder => "vcylinder",
+            Boundary::Reflect => "reflect",
+        }
+    }
+
+    /// Find a boundary by name, ignoring case, also accepting torus for wrap
+    pub fn from_name(name: &str) -> Option<Boundary> {
+        if name.eq_ignore_ascii_case("torus") {
+            return Some(Boundary::Wrap);
+        }
+        BOUNDARIES.iter().find(|b| b.name().eq_ignore_ascii_case(name)).copied()
+    }
+
+    /// Get the next option when cycling through boundaries
+    pub fn next(&self) -> Boundary {
+        let index = BOUNDARIES.iter().position(|b| b == self).unwrap_or(0);
+        BOUNDARIES[(index + 1) % BOUNDARIES.len()]
+    }
+
+    /// Get the text shown on the button for this option
+    pub fn label(&self) -> &'static str {
+        match self {
+            Boundary::Void => "VOID",
+            Boundary::Wrap => "WRAP",
+            Boundary::Klein => "KLEIN",
+            Boundary::Cross => "CROSS",
+            Boundary::Sphere => "SPHERE",
+            Boundary::HorizontalCylinder => "H-CYLINDER",
+            Boundary::VerticalCylinder => "V-CYLINDER",
+            Boundary::Reflect => "REFLECT",
+        }
+    }
+
+    /// Whether crossing the left or right edge can lead to a different row
+    pub fn crosses_rows(&self) -> bool {
+        matches!(self, Boundary::Cross | Boundary::Sphere)
+    }
+
+    /// Find the cell at the given row and column, which may lie beyond the edges of a world of
+    /// the given size, returning None if it is beyond an edge where everything is dead
+    /// The sphere joins edges of the same length, so on a world that is not square any cell
+    /// whose joined edge is too short is treated as dead
+    pub fn resolve(&self, row: isize, col: isize, rows: usize, cols: usize) -> Option<(usize, usize)> {
+        let (rows, cols) = (rows as isize, cols as isize);
+        let (mut row, mut col) = (row, col);
+
+        // a cell beyond a corner crosses two edges, one after the other
+        for _ in 0..2 {
+            let is_row_outside: bool = row < 0 || row >= rows;
+            let is_col_outside: bool = col < 0 || col >= cols;
+            if !is_row_outside && !is_col_outside {
+                return Some((row as usize, col as usize));
+            }
+
+            match self {
+                Boundary::Void => return None,
+                Boundary::Wrap => {
+                    row = row.rem_euclid(rows);
+                    col = col.rem_euclid(cols);
+                },
+                // each time round a twisted pair of edges mirrors the other way, so only an odd
+                // number of times round leaves the cell mirrored
+                Boundary::Klein => {
+                    if is_row_outside {
+                        if row.div_euclid(rows) % 2 != 0 {
+                            col = cols - 1 - col;
+                        }
+                        row = row.rem_euclid(rows);
+                    } else {
+                        col = col.rem_euclid(cols);
+                    }
+                },
+                Boundary::Cross => {
+                    if is_row_outside {
+                        if row.div_euclid(rows) % 2 != 0 {
+                            col = cols - 1 - col;
+                        }
+                        row = row.rem_euclid(rows);
+                    } else {
+                        if col.div_euclid(cols) % 2 != 0 {
+                            row = rows - 1 - row;
+                        }
+                        col = col.rem_euclid(cols);
+                    }
+                },
+                Boundary::Sphere => {
+                    // swap the roles of the row and column, mirroring the edge being crossed
+                    (row, col) = if row < 0 {
+                        (col, -1 - row)
+                    } else if row >= rows {
+                        (col, cols - 1 - (row - rows))
+                    } else if col < 0 {
+                        (-1 - col, row)
+                    } else {
+                        (rows - 1 - (col - cols), row)
+                    };
+
+                    // the position along the edge crossed must fit along the edge it joins,
+                    // which is only checked away from the corners as they cross twice
+                    let is_corner: bool = is_row_outside && is_col_outside;
+                    if !is_corner && (row >= rows || col >= cols) {
+                        return None;
+                    }
+                },
+                Boundary::HorizontalCylinder => {
+                    if is_row_outside {
+                        return None;
+                    }
+                    col = col.rem_euclid(cols);
+                },
+                Boundary::VerticalCylinder => {
+                    if is_col_outside {
+                        return None;
+                    }
+                    row = row.rem_euclid(rows);
+                },
+                Boundary::Reflect => {
+                    row = reflect(row, rows);
+                    col = reflect(col, cols);
+                },
+            }
+        }
+
+        // a cell beyond a corner is only brought inside by the second pass, so check it again
+        if (0..rows).contains(&row) && (0..cols).contains(&col) {
+            Some((row as usize, col as usize))
+        } else {
+            None
+        }
+    }
+}
+
+/// Mirror a position into a line of the given length, whose ends repeat it back and forth
+fn reflect(position: isize, length: isize) -> isize {
+    let position: isize = position.rem_euclid(2 * length);
+    if position < length { position } else { 2 * length - 1 - position }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inside_cells_are_unchanged() {
+        for boundary in BOUNDARIES {
+            assert_eq!(boundary.resolve(2, 3, 5, 7), Some((2, 3)));
+        }
+    }
+
+    #[test]
+    fn edges_are_joined() {
+        assert_eq!(Boundary::Void.resolve(-1, 3, 5, 7), None);
+        assert_eq!(Boundary::Wrap.resolve(-1, 7, 5, 7), Some((4, 0)));
+        assert_eq!(Boundary::Klein.resolve(-1, 1, 5, 7), Some((4, 5)));
+        assert_eq!(Boundary::Klein.resolve(1, -1, 5, 7), Some((1, 6)));
+        assert_eq!(Boundary::Cross.resolve(1, -1, 5, 7), Some((3, 6)));
+        assert_eq!(Boundary::HorizontalCylinder.resolve(2, 7, 5, 7), Some((2, 0)));
+        assert_eq!(Boundary::HorizontalCylinder.resolve(5, 2, 5, 7), None);
+        assert_eq!(Boundary::VerticalCylinder.resolve(5, 2, 5, 7), Some((0, 2)));
+        assert_eq!(Boundary::VerticalCylinder.resolve(2, 7, 5, 7), None);
+        assert_eq!(Boundary::Reflect.resolve(-1, 7, 5, 7), Some((0, 6)));
+    }
+
+    #[test]
+    fn offsets_beyond_the_world_go_round_again() {
+        // twice round a twisted pair of edges undoes the twist
+        assert_eq!(Boundary::Klein.resolve(-6, 1, 5, 7), Some((4, 1)));
+        assert_eq!(Boundary::Klein.resolve(-11, 1, 5, 7), Some((4, 5)));
+        assert_eq!(Boundary::Klein.resolve(12, 1, 5, 7), Some((2, 1)));
+        assert_eq!(Boundary::Cross.resolve(1, -8, 5, 7), Some((1, 6)));
+        assert_eq!(Boundary::Cross.resolve(1, 22, 5, 7), Some((3, 1)));
+        assert_eq!(Boundary::Wrap.resolve(-11, 16, 5, 7), Some((4, 2)));
+
+        // mirrors reflect each cell beyond them, not just the first
+        assert_eq!(Boundary::Reflect.resolve(-3, 2, 5, 7), Some((2, 2)));
+        assert_eq!(Boundary::Reflect.resolve(2, 9, 5, 7), Some((2, 4)));
+        assert_eq!(Boundary::Reflect.resolve(-7, 2, 5, 7), Some((3, 2)));
+    }
+
+    #[test]
+    fn sphere_joins_adjacent_edges() {
+        // top to left and bottom to right, in both directions
+        assert_eq!(Boundary::Sphere.resolve(-1, 3, 6, 6), Some((3, 0)));
+        assert_eq!(Boundary::Sphere.resolve(3, -1, 6, 6), Some((0, 3)));
+        assert_eq!(Boundary::Sphere.resolve(6, 2, 6, 6), Some((2, 5)));
+        assert_eq!(Boundary::Sphere.resolve(2, 6, 6, 6), Some((5, 2)));
+
+        // every cell beyond the edges of a square world is found somewhere
+        for row in -1..=6 {
+            for col in -1..=6 {
+                assert!(Boundary::Sphere.resolve(row, col, 6, 6).is_some());
+            }
+        }
+
+        // a long edge with nothing to join to is dead
+        assert_eq!(Boundary::Sphere.resolve(-1, 5, 4, 6), None);
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for boundary in BOUNDARIES {
+            assert_eq!(Boundary::from_name(boundary.name()), Some(boundary));
+        }
+        assert_eq!(Boundary::from_name("TORUS"), Some(Boundary::Wrap));
+    }
+}
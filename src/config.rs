@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::keys::{default_bindings, Action};
+use crate::life::{Anchor, FillRegion, UpdateMode};
+use crate::ui::{GridLines, ViewMode};
+use crate::{DEFAULT_FILL_BOX_SIZE, DEFAULT_FILL_DENSITY, DEFAULT_FRAME_CAP, DEFAULT_GRID_FADE_SIZE, DEFAULT_SPEED, DEFAULT_UPDATE_PROBABILITY, DEFAULT_WINDOW_HEIGHT, DEFAULT_WINDOW_WIDTH};
+
+// location of the configuration file within the user's config directory
+const CONFIG_DIR: &str = "conway";
+const CONFIG_FILE: &str = "config.toml";
+const RULES_DIR: &str = "rules";
+
+/// Size and position of the main window
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            width: DEFAULT_WINDOW_WIDTH,
+            height: DEFAULT_WINDOW_HEIGHT,
+            x: None,
+            y: None,
+        }
+    }
+}
+
+/// How random soups are generated
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FillConfig {
+    pub density: f64,
+    pub region: FillRegion,
+    pub box_size: usize,
+    pub seed: Option<u64>,
+}
+
+impl Default for FillConfig {
+    fn default() -> Self {
+        FillConfig {
+            density: DEFAULT_FILL_DENSITY,
+            region: FillRegion::Whole,
+            box_size: DEFAULT_FILL_BOX_SIZE,
+            seed: None,
+        }
+    }
+}
+
+/// How cells are updated each generation, and how much noise is added
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdateConfig {
+    pub mode: UpdateMode,
+    pub probability: f64,
+    pub noise: f64,
+    pub seed: Option<u64>,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        UpdateConfig {
+            mode: UpdateMode::Synchronous,
+            probability: DEFAULT_UPDATE_PROBABILITY,
+            noise: 0.0,
+            seed: None,
+        }
+    }
+}
+
+/// User preferences that persist between runs
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub theme: String,
+    pub rule: String,
+    pub boundary: String,
+    pub speed: u32,
+    pub grid_lines: GridLines,
+    pub grid_fade_size: i32,
+    pub view: ViewMode,
+    pub vsync: bool,
+    pub frame_cap: u32,
+    pub track_window: bool,
+    pub resize_anchor: Anchor,
+    pub last_directory: Option<PathBuf>,
+    pub window: WindowConfig,
+    pub fill: FillConfig,
+    pub update: UpdateConfig,
+    pub keys: BTreeMap<Action, Vec<String>>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            theme: "light".to_string(),
+            rule: "B3/S23".to_string(),
+            boundary: "void".to_string(),
+            speed: DEFAULT_SPEED,
+            grid_lines: GridLines::Minor,
+            grid_fade_size: DEFAULT_GRID_FADE_SIZE,
+            view: ViewMode::Plain,
+            vsync: true,
+            frame_cap: DEFAULT_FRAME_CAP,
+            track_window: false,
+            resize_anchor: Anchor::Center,
+            last_directory: None,
+            window: WindowConfig::default(),
+            fill: FillConfig::default(),
+            update: UpdateConfig::default(),
+            keys: default_bindings(),
+        }
+    }
+}
+
+impl Config {
+    /// Get the path of the configuration file, if the platform has a config directory
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(CONFIG_FILE))
+    }
+
+    /// Get the directory where .rule files can be found by name, next to the configuration file
+    pub fn rules_dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(RULES_DIR))
+    }
+
+    /// Read the configuration file, falling back to the defaults if it is missing or invalid
+    pub fn load() -> Config {
+        let path: PathBuf = match Config::path() {
+            Some(p) => p,
+            None => return Config::default(),
+        };
+
+        let contents: String = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(_) => return Config::default(),
+        };
+
+        match toml::from_str(&contents) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("WARNING: Ignoring invalid config file {}: {}", path.display(), e);
+                Config::default()
+            },
+        }
+    }
+
+    /// Write the configuration file, creating its directory if needed
+    pub fn save(&self) -> Result<(), String> {
+        let path: PathBuf = match Config::path() {
+            Some(p) => p,
+            None => return Err("No config directory available".to_string()),
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let contents: String = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&path, contents).map_err(|e| e.to_string())
+    }
+}
@@ -1,320 +1,267 @@
-use crate::draw::{fill_triangle, interpolate};
+use crate::keys::{Action, Keymap};
 use crate::text::TextCache;
-use crate::ui::{BUFFER_SIZE, Vector2};
+use crate::theme::Theme;
+use crate::ui::BUFFER_SIZE;
+use crate::widget::{render_tooltip, Button, Icon, IconButton, Label, Slider, Widget, WidgetState};
 
-use sdl2::pixels::Color;
-use sdl2::rect::{Point, Rect};
-use sdl2::render::{Canvas, Texture};
-use sdl2::video::Window;
+use sdl2::image::ImageRWops;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::rwops::RWops;
+use sdl2::surface::Surface;
+use sdl2::video::{Window, WindowContext};
 
-// control dimension constants
-const HEIGHT: i32 = BUFFER_SIZE / 2;
-const PLAY_BUTTON_WIDTH: i32 = ((HEIGHT / 2) as f32 * 1.7321) as i32;
-const PAUSE_BUTTON_WIDTH: i32 = HEIGHT / 4;
-const PAUSE_BUTTON_DIST: i32 = HEIGHT / 3;
-
-// padding on top and on the bottom for the controls
-const PADDING_TOP: i32 = (BUFFER_SIZE - HEIGHT) / 2;
-const PADDING_BOTTOM: i32 = BUFFER_SIZE - PADDING_TOP - HEIGHT;
-
-// location of slider
-const SLIDER_X: i32 = 60;
-const SLIDER_WIDTH: i32 = 120;
-const SLIDER_PADDING: i32 = 2;
+use std::time::{Duration, Instant};
 
-// location of wrap button
-const WRAP_X_RIGHT: i32 = 60;  // x-value for top-right, calculated from the right-end
-const WRAP_TEXT_PADDING: i32 = 10;
-
-// icon size and locations
+// height of buttons and the slider, and size of icons
+const HEIGHT: i32 = BUFFER_SIZE / 2;
 const ICON_SIZE: i32 = 20;
-const UPLOAD_X: i32 = 60;
 
-/// Render a upload icon in the top buffer
-pub fn render_upload(canvas: &mut Canvas<Window>, icon: &Texture) {
-    // create rectangle for icon
-    let upload_rect = Rect::new(UPLOAD_X,
-                                (BUFFER_SIZE - ICON_SIZE) / 2,
-                                ICON_SIZE as u32,
-                                ICON_SIZE as u32);
+// width of the speed slider
+const SLIDER_WIDTH: i32 = 120;
 
-    // copy texture
-    canvas.copy(icon, None, Some(upload_rect)).unwrap();
+// space between neighbouring controls in the same buffer
+const SPACING: i32 = 20;
+
+// how long the mouse must rest on a control before its tooltip is shown
+const TOOLTIP_DELAY: Duration = Duration::from_millis(600);
+
+// layout of overlays such as the help
+const OVERLAY_WIDTH: i32 = 460;
+const OVERLAY_LINE_HEIGHT: i32 = 22;
+const OVERLAY_PADDING: i32 = 20;
+
+/// Load a PNG icon as a white mask, so that it can be tinted to match the theme
+pub fn load_icon<'a>(
+    texture_creator: &'a TextureCreator<WindowContext>,
+    bytes: &[u8],
+) -> Result<Texture<'a>, String> {
+    let surface: Surface = RWops::from_bytes(bytes)?.load_png()?;
+    let mut surface: Surface = surface.convert_format(PixelFormatEnum::RGBA32)?;
+
+    // keep only the alpha channel of the icon
+    surface.with_lock_mut(|pixels: &mut [u8]| {
+        for pixel in pixels.chunks_mut(4) {
+            pixel[0] = 255;
+            pixel[1] = 255;
+            pixel[2] = 255;
+        }
+    });
+
+    texture_creator.create_texture_from_surface(&surface).map_err(|e| e.to_string())
 }
 
-/// Render a play button in the bottom buffer
-pub fn render_play(canvas: &mut Canvas<Window>) {
-    // get screen size and set draw color
-    let screen_size: (u32, u32) = canvas.output_size().unwrap();
-    let screen_width = screen_size.0 as i32;
-    let screen_height = screen_size.1 as i32;
-    canvas.set_draw_color(Color::BLACK);
-
-    // points for the play button triangle
-    let a = Vector2::new((screen_width - PLAY_BUTTON_WIDTH) / 2, 
-        screen_height - BUFFER_SIZE + PADDING_TOP);
-    let b = Vector2::new((screen_width - PLAY_BUTTON_WIDTH) / 2, 
-        screen_height - PADDING_BOTTOM);
-    let c = Vector2::new((screen_width + PLAY_BUTTON_WIDTH) / 2, 
-        screen_height - PADDING_BOTTOM - HEIGHT / 2);
-
-    // render the triangle
-    fill_triangle(canvas, a, b, c);
+/// Get the rectangle for a control of the given size in the top or bottom buffer, with its left
+/// edge at x, centred vertically in the buffer
+fn in_buffer(x: i32, size: (i32, i32), screen_height: i32, is_top: bool) -> Rect {
+    let buffer_y: i32 = if is_top { 0 } else { screen_height - BUFFER_SIZE };
+    Rect::new(x, buffer_y + (BUFFER_SIZE - size.1) / 2, size.0 as u32, size.1 as u32)
 }
 
-/// Render a pause button in the bottom buffer
-pub fn render_pause(canvas: &mut Canvas<Window>) { 
-    // get screen size and set draw color
-    let screen_size: (u32, u32) = canvas.output_size().unwrap();
-    let screen_width = screen_size.0 as i32;
-    let screen_height = screen_size.1 as i32;
-    canvas.set_draw_color(Color::BLACK);
-
-    // create the rectangles
-    let left_rect = Rect::new((screen_width - PAUSE_BUTTON_DIST) / 2 - PAUSE_BUTTON_WIDTH,
-        screen_height - PADDING_BOTTOM - HEIGHT,
-        PAUSE_BUTTON_WIDTH as u32,
-        HEIGHT as u32);
-    let right_rect = Rect::new((screen_width + PAUSE_BUTTON_DIST) / 2,
-        screen_height - PADDING_BOTTOM - HEIGHT,
-        PAUSE_BUTTON_WIDTH as u32,
-        HEIGHT as u32);
-
-    // render the rectangles
-    canvas.fill_rect(left_rect).unwrap();
-    canvas.fill_rect(right_rect).unwrap();
+/// Every control drawn around the grid
+pub struct Controls<'a> {
+    pub play: IconButton<'a>,
+    pub speed: Slider,
+    pub boundary: Button,
+    pub upload: IconButton<'a>,
+    pub grid_lines: Button,
+    pub view: Button,
+    pub theme: Button,
+    pub status: Label,
+    hover_started: Option<Instant>,
 }
 
-/// Render a button that shows whether it is using wrap-around grids
-pub fn render_wrap(canvas: &mut Canvas<Window>, text_cache: &mut TextCache, is_wrap: bool) {
-    // get screen size and set draw color
-    let screen_size: (u32, u32) = canvas.output_size().unwrap();
-    let screen_width = screen_size.0 as i32;
-    let screen_height = screen_size.1 as i32;
-    canvas.set_draw_color(Color::BLACK);
-    let mut button_text = "VOID";
-    if is_wrap {
-        button_text = "WRAP";
+impl<'a> Controls<'a> {
+    /// Create the controls, using the given texture as the upload icon
+    pub fn new(upload_icon: Texture<'a>) -> Self {
+        Controls {
+            play: IconButton::new(Icon::Play, HEIGHT, "Play / pause"),
+            speed: Slider::new(SLIDER_WIDTH, HEIGHT, "Generations per second"),
+            boundary: Button::new(HEIGHT, "Boundary"),
+            upload: IconButton::new(Icon::Image(upload_icon), ICON_SIZE, "Load pattern"),
+            grid_lines: Button::new(HEIGHT, "Grid lines"),
+            view: Button::new(HEIGHT, "View mode"),
+            theme: Button::new(HEIGHT, "Theme"),
+            status: Label::default(),
+            hover_started: None,
+        }
     }
-    
-    // get the text texture
-    let dimensions: (i32, i32) = text_cache.get_dimensions(button_text);
-    let text_texture: &Texture = text_cache.render_text(button_text);
-    let text_x: i32 = screen_width - WRAP_X_RIGHT - dimensions.0 - WRAP_TEXT_PADDING;
-    let text_y: i32 = screen_height - PADDING_BOTTOM - (HEIGHT + dimensions.1) / 2;
-    
-    // create the rectangles
-    let outer_rect = Rect::new(
-        screen_width - WRAP_X_RIGHT - dimensions.0 - WRAP_TEXT_PADDING * 2,
-        screen_height - PADDING_BOTTOM - HEIGHT,
-        (dimensions.0 + WRAP_TEXT_PADDING * 2) as u32,
-        HEIGHT as u32);
-    let text_rect = Rect::new(text_x, text_y, dimensions.0 as u32, dimensions.1 as u32);
 
-    // render the rectangles
-    canvas.draw_rect(outer_rect).unwrap();
-    canvas.copy(text_texture, None, text_rect).unwrap();
-}
-
-/// Render a slider for controlling the speed of the simulation
-/// The length of the inner slider is controlled by a number from 0 to 1
-pub fn render_slider(
-    canvas: &mut Canvas<Window>, 
-    text_cache: &mut TextCache,
-    speed_text: &str,
-    slider_len: f32,
-) {
-    // get screen size and set draw color
-    let screen_size: (u32, u32) = canvas.output_size().unwrap();
-    let screen_height = screen_size.1 as i32;
-    canvas.set_draw_color(Color::BLACK);
-
-    // create the rectangles
-    let outer_rect = Rect::new(SLIDER_X,
-        screen_height - PADDING_BOTTOM - HEIGHT,
-        SLIDER_WIDTH as u32,
-        HEIGHT as u32);
-    let inner_rect = Rect::new(SLIDER_X + SLIDER_PADDING,
-        screen_height - PADDING_BOTTOM - HEIGHT + SLIDER_PADDING,
-        ((SLIDER_WIDTH - 2 * SLIDER_PADDING) as f32 * slider_len) as u32,
-        (HEIGHT - 2 * SLIDER_PADDING) as u32);
-    
-    // render the rectangles
-    canvas.draw_rect(outer_rect).unwrap();
-    canvas.set_draw_color(Color::RGB(192, 192, 192));
-    canvas.fill_rect(inner_rect).unwrap();
-
-    // render the speed text
-    let dimensions: (i32, i32) = text_cache.get_dimensions(speed_text);
-    let text_texture: &Texture = text_cache.render_text(speed_text);
-    let text_x: i32 =
-        SLIDER_X + ((SLIDER_WIDTH - dimensions.0) as f32 / 2.0) as i32;
-    let text_y: i32 =
-        screen_height - PADDING_BOTTOM - ((HEIGHT + dimensions.1) as f32 / 2.0) as i32;
-    canvas
-        .copy(
-            text_texture,
-            None,
-            Some(Rect::new(text_x, text_y, dimensions.0 as u32, dimensions.1 as u32)),
-        )
-        .unwrap();
-}
-
-/// Given x and y coordinates, check to see if it is within the play button
-pub fn in_play(canvas: &Canvas<Window>, x: i32, y: i32) -> bool {
-    // get screen size
-    let screen_size: (u32, u32) = canvas.output_size().unwrap();
-    let screen_width = screen_size.0 as i32;
-    let screen_height = screen_size.1 as i32;
-
-    // points for the play button triangle
-    let a = Vector2::new((screen_width - PLAY_BUTTON_WIDTH) / 2, 
-        screen_height - BUFFER_SIZE + PADDING_TOP);
-    let b = Vector2::new((screen_width - PLAY_BUTTON_WIDTH) / 2, 
-        screen_height - PADDING_BOTTOM);
-    let c = Vector2::new((screen_width + PLAY_BUTTON_WIDTH) / 2, 
-        screen_height - PADDING_BOTTOM - HEIGHT / 2);
-
-    // sort the points in increasing order by y-level
-    let mut points: Vec<Vector2> = vec![a, b, c];
-    points.sort_by(|first, second| first.y.cmp(&second.y));
-
-    // ensure given y-value is within the least and greatest y-values of the triangle
-    if y < points[0].y || y > points[2].y {
-        return false;
+    /// Get every control, in the order they are rendered
+    fn widgets(&self) -> [&dyn Widget; 8] {
+        [&self.play, &self.speed, &self.boundary, &self.upload,
+         &self.grid_lines, &self.view, &self.theme, &self.status]
     }
 
-    // compute active edges
-    let left_edge: (Vector2, Vector2);
-    let right_edge: (Vector2, Vector2);
-    
-    /*
-    configuration where the intermediate point is to the right of the lowest point:
-     -
-     |\
-     | \
-     |  -
-     | /
-     |/
-     -
-     */
-    if points[0].x <= points[1].x && y <= points[1].y {
-        left_edge = (points[0], points[2]);
-        right_edge = (points[0], points[1]);
-    } else if points[0].x <= points[1].x {
-        left_edge = (points[0], points[2]);
-        right_edge = (points[1], points[2]);
+    /// Get every control mutably, in the order they are rendered
+    fn widgets_mut(&mut self) -> [&mut dyn Widget; 8] {
+        [&mut self.play, &mut self.speed, &mut self.boundary, &mut self.upload,
+         &mut self.grid_lines, &mut self.view, &mut self.theme, &mut self.status]
     }
 
-    /*
-    configuration where the intermediate point is to the left of the lowest point:
-        -
-       /|
-      / |
-     -  |
-      \ |
-       \|
-        -
-    */
-    else if y <= points[1].y {
-        left_edge = (points[0], points[1]);
-        right_edge = (points[0], points[2]);
-    } else {
-        left_edge = (points[1], points[2]);
-        right_edge = (points[0], points[2]);
+    /// Place the controls on a screen of the given size, after their contents have been updated
+    /// The top buffer holds the upload icon and view buttons on the left, a status label in the
+    /// middle and the theme on the right; the bottom buffer holds the speed slider on the left,
+    /// play button in the middle and boundary on the right
+    pub fn layout(&mut self, screen_size: (u32, u32), text_cache: &TextCache) {
+        let screen_width = screen_size.0 as i32;
+        let screen_height = screen_size.1 as i32;
+
+        // controls flowing from the left edge of the grid
+        let mut x: i32 = BUFFER_SIZE;
+        for widget in [&mut self.upload as &mut dyn Widget, &mut self.grid_lines, &mut self.view] {
+            let size: (i32, i32) = widget.size(text_cache);
+            widget.place(in_buffer(x, size, screen_height, true));
+            x += size.0 + SPACING;
+        }
+        let size: (i32, i32) = self.speed.size(text_cache);
+        self.speed.place(in_buffer(BUFFER_SIZE, size, screen_height, false));
+
+        // controls in the middle of the screen
+        let size: (i32, i32) = self.status.size(text_cache);
+        self.status.place(in_buffer((screen_width - size.0) / 2, size, screen_height, true));
+        let size: (i32, i32) = self.play.size(text_cache);
+        self.play.place(in_buffer((screen_width - size.0) / 2, size, screen_height, false));
+
+        // controls ending at the right edge of the grid
+        let size: (i32, i32) = self.theme.size(text_cache);
+        self.theme.place(in_buffer(screen_width - BUFFER_SIZE - size.0, size, screen_height, true));
+        let size: (i32, i32) = self.boundary.size(text_cache);
+        self.boundary.place(in_buffer(screen_width - BUFFER_SIZE - size.0, size, screen_height, false));
     }
-    
-    // get start and end x-values of the play button in the given y-level
-    let start: i32 = interpolate(left_edge.0, left_edge.1, y);
-    let end: i32 = interpolate(right_edge.0, right_edge.1, y);
-
-    x >= start && x <= end
-}
 
-/// Given x and y coordinates, check to see if it is within the pause button
-pub fn in_pause(canvas: &Canvas<Window>, x: i32, y: i32) -> bool {
-    // get screen size and click point
-    let screen_size: (u32, u32) = canvas.output_size().unwrap();
-    let screen_width = screen_size.0 as i32;
-    let screen_height = screen_size.1 as i32;
-
-    // create the pause bounding rectangle
-    let pause_rect = Rect::new((screen_width - PAUSE_BUTTON_DIST) / 2 - PAUSE_BUTTON_WIDTH,
-        screen_height - PADDING_BOTTOM - HEIGHT,
-        (2 * PAUSE_BUTTON_WIDTH + PAUSE_BUTTON_DIST) as u32,
-        HEIGHT as u32);
+    /// Render every control
+    pub fn render(&mut self, canvas: &mut Canvas<Window>, theme: &Theme, text_cache: &mut TextCache) {
+        for widget in self.widgets_mut() {
+            widget.render(canvas, theme, text_cache);
+        }
+    }
 
-    pause_rect.contains_point(Point::new(x, y))
-}
+    /// Update which control the mouse is over and whether it is held down on it
+    /// Returns whether anything changed, so that the screen can be redrawn
+    pub fn update_mouse(&mut self, x: i32, y: i32, is_down: bool) -> bool {
+        let previous: Option<usize> = self.hovered_index();
+        let mut is_changed = false;
+        for widget in self.widgets_mut() {
+            let is_hovered: bool = widget.contains(x, y);
+            let state = WidgetState { is_hovered, is_pressed: is_hovered && is_down };
+            if widget.state() != state {
+                is_changed = true;
+                widget.set_state(state);
+            }
+        }
+
+        // restart the tooltip delay whenever the mouse moves onto a different control
+        let current: Option<usize> = self.hovered_index();
+        if current != previous {
+            self.hover_started = current.map(|_| Instant::now());
+        }
+        is_changed
+    }
 
-/// Given x and y coordinates, check to see if it is within the upload icon
-pub fn in_upload(x: i32, y: i32) -> bool {
-    // create the bounding rectangle for the upload icon
-    let upload_rect = Rect::new(UPLOAD_X,
-                                (BUFFER_SIZE - ICON_SIZE) / 2,
-                                ICON_SIZE as u32,
-                                ICON_SIZE as u32);
+    /// Get the position of the control the mouse is over in the list of controls, if any
+    fn hovered_index(&self) -> Option<usize> {
+        self.widgets().iter().position(|widget| widget.state().is_hovered)
+    }
 
-    upload_rect.contains_point(Point::new(x, y))
-}
+    /// Check whether the mouse is over any control
+    pub fn is_hovered(&self) -> bool {
+        self.hovered_index().is_some()
+    }
 
-/// Given x, y coordinates and the wrap state, check to see if it is within the wrap button
-pub fn in_wrap(
-    canvas: &Canvas<Window>,
-    text_cache: &TextCache,
-    x: i32,
-    y: i32,
-    is_wrap: bool
-) -> bool {
-    // get screen size and set draw color
-    let screen_size: (u32, u32) = canvas.output_size().unwrap();
-    let screen_width = screen_size.0 as i32;
-    let screen_height = screen_size.1 as i32;
-    let mut button_text = "VOID";
-    if is_wrap {
-        button_text = "WRAP";
+    /// Check whether the mouse is resting on a control whose tooltip has not been shown yet
+    pub fn is_tooltip_pending(&self) -> bool {
+        self.hover_started.is_some_and(|start| start.elapsed() < TOOLTIP_DELAY)
     }
-    
-    // get the text texture dimensions
-    let dimensions: (i32, i32) = text_cache.get_dimensions(button_text);
 
-    // get the bounding rectangle
-    let outer_rect = Rect::new(
-        screen_width - WRAP_X_RIGHT - dimensions.0 - WRAP_TEXT_PADDING * 2,
-        screen_height - PADDING_BOTTOM - HEIGHT,
-        (dimensions.0 + WRAP_TEXT_PADDING * 2) as u32,
-        HEIGHT as u32);
+    /// Render the tooltip of the control the mouse has rested on, along with the keys bound to
+    /// the control's action, unless the control is being pressed
+    pub fn render_tooltip(
+        &self,
+        canvas: &mut Canvas<Window>,
+        theme: &Theme,
+        text_cache: &mut TextCache,
+        keymap: &Keymap,
+    ) {
+        if self.hover_started.is_none() || self.is_tooltip_pending() {
+            return;
+        }
+        let widget: &dyn Widget = match self.hovered_index() {
+            Some(index) => self.widgets()[index],
+            None => return,
+        };
+        if widget.state().is_pressed {
+            return;
+        }
+        let tooltip: &str = match widget.tooltip() {
+            Some(t) => t,
+            None => return,
+        };
+
+        let rect: Rect = widget.rect();
+        let text: String = match self.action_at(rect.center().x(), rect.center().y()) {
+            Some(action) if !keymap.keys(action).is_empty() => {
+                format!("{} ({})", tooltip, keymap.keys(action))
+            },
+            _ => tooltip.to_string(),
+        };
+        render_tooltip(canvas, theme, text_cache, &text, rect);
+    }
 
-    outer_rect.contains_point(Point::new(x, y))
+    /// Get the action triggered by clicking at the given screen coordinates, if any
+    /// The speed slider is dragged rather than clicked, so it does not trigger an action
+    pub fn action_at(&self, x: i32, y: i32) -> Option<Action> {
+        let buttons: [(&dyn Widget, Action); 6] = [
+            (&self.play, Action::PlayPause),
+            (&self.boundary, Action::NextBoundary),
+            (&self.upload, Action::Load),
+            (&self.grid_lines, Action::NextGridLines),
+            (&self.view, Action::NextView),
+            (&self.theme, Action::NextTheme),
+        ];
+        buttons.iter().find(|(widget, _)| widget.contains(x, y)).map(|(_, action)| *action)
+    }
 }
 
-/// Given x and y coordinates, check to see if it is within the slider
-pub fn in_slider(canvas: &Canvas<Window>, x: i32, y: i32) -> bool {
-    // get screen size and click point
+/// Render a panel in the middle of the screen over a translucent backdrop, such as the help,
+/// with a line for each pair of texts split into two columns
+pub fn render_overlay(
+    canvas: &mut Canvas<Window>,
+    theme: &Theme,
+    text_cache: &mut TextCache,
+    lines: &[(&str, String)],
+) {
     let screen_size: (u32, u32) = canvas.output_size().unwrap();
+    let screen_width = screen_size.0 as i32;
     let screen_height = screen_size.1 as i32;
 
-    // create the pause bounding rectangle
-    let outer_rect = Rect::new(SLIDER_X,
-        screen_height - PADDING_BOTTOM - HEIGHT,
-        SLIDER_WIDTH as u32,
-        HEIGHT as u32);
-
-    outer_rect.contains_point(Point::new(x, y))
-}
-
-/// Given x coordinates, calculate how long the slider should be
-pub fn calc_slider(x: i32) -> f32 {
-    // calculate bounds
-    let left_bound: i32 = SLIDER_X;
-    let right_bound: i32 = SLIDER_X + SLIDER_WIDTH;
-
-    if x <= left_bound {
-        return 0.0;
-    } else if x >= right_bound {
-        return 1.0;
-    } else {
-        return (x - SLIDER_X) as f32 / SLIDER_WIDTH as f32;
+    // create the panel, drawn over a translucent backdrop
+    let height: i32 = lines.len() as i32 * OVERLAY_LINE_HEIGHT + OVERLAY_PADDING * 2;
+    let panel = Rect::new((screen_width - OVERLAY_WIDTH) / 2,
+        (screen_height - height) / 2,
+        OVERLAY_WIDTH as u32,
+        height as u32);
+    let mut backdrop = theme.background;
+    backdrop.a = 160;
+    canvas.set_draw_color(backdrop);
+    canvas.fill_rect(None).unwrap();
+    canvas.set_draw_color(theme.background);
+    canvas.fill_rect(panel).unwrap();
+    canvas.set_draw_color(theme.control);
+    canvas.draw_rect(panel).unwrap();
+
+    // render the first text in the left column and the second in the right column
+    for (i, (left, right)) in lines.iter().enumerate() {
+        let y: i32 = panel.y() + OVERLAY_PADDING + i as i32 * OVERLAY_LINE_HEIGHT;
+        for (text, x) in [(*left, panel.x() + OVERLAY_PADDING), (right.as_str(), panel.center().x())] {
+            if text.is_empty() {
+                continue;
+            }
+            let dimensions: (i32, i32) = text_cache.get_dimensions(text);
+            let text_texture: &Texture = text_cache.render_text(text, theme.text);
+            let text_rect = Rect::new(x, y, dimensions.0 as u32, dimensions.1 as u32);
+            canvas.copy(text_texture, None, text_rect).unwrap();
+        }
     }
 }
-
@@ -0,0 +1,256 @@
+use crate::file::bounds;
+use crate::life::Grid;
+
+use std::collections::HashMap;
+
+// most cells of earlier generations remembered at once, after which the search starts over from
+// the latest
+const MAX_TRACKED_CELLS: usize = 20_000_000;
+
+/// How a pattern repeats, found once it returns to an earlier generation
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Periodicity {
+    /// Every cell is dead
+    Empty,
+    /// The pattern comes back after the period, moved by the displacement in (rows, cols)
+    Periodic { period: u64, displacement: (isize, isize) },
+}
+
+impl Periodicity {
+    /// Get the short description shown at the top of the window
+    pub fn label(&self) -> String {
+        match *self {
+            Periodicity::Empty => "EMPTY".to_string(),
+            Periodicity::Periodic { period: 1, displacement: (0, 0) } => "STILL LIFE".to_string(),
+            Periodicity::Periodic { period, displacement: (0, 0) } => format!("P{} OSCILLATOR", period),
+            Periodicity::Periodic { period, displacement } => {
+                // the c in speeds stands for the speed of light, and is kept lowercase
+                let speed: String = speed(period, displacement);
+                let (notation, direction) = speed.split_once(' ').unwrap_or((&speed, ""));
+                format!("P{} SPACESHIP {} {}", period, notation, direction.to_uppercase())
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Periodicity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Periodicity::Empty => write!(f, "empty"),
+            Periodicity::Periodic { period: 1, displacement: (0, 0) } => write!(f, "still life"),
+            Periodicity::Periodic { period, displacement: (0, 0) } => write!(f, "period {} oscillator", period),
+            Periodicity::Periodic { period, displacement: (rows, cols) } => {
+                let mut moves: Vec<String> = Vec::new();
+                if rows != 0 {
+                    moves.push(format!("{} {}", rows.unsigned_abs(), if rows > 0 { "down" } else { "up" }));
+                }
+                if cols != 0 {
+                    moves.push(format!("{} {}", cols.unsigned_abs(), if cols > 0 { "right" } else { "left" }));
+                }
+                write!(f, "period {} spaceship moving {}, at {}", period, moves.join(" and "), speed(period, (rows, cols)))
+            },
+        }
+    }
+}
+
+/// Get the speed of a spaceship in the usual notation, such as c/4 diagonal for the glider,
+/// c/2 orthogonal for the lightweight spaceship and (2,1)c/6 oblique for knightships
+fn speed(period: u64, displacement: (isize, isize)) -> String {
+    let (rows, cols) = (displacement.0.unsigned_abs() as u64, displacement.1.unsigned_abs() as u64);
+    if rows != cols && rows != 0 && cols != 0 {
+        return format!("({},{})c/{} oblique", rows.max(cols), rows.min(cols), period);
+    }
+
+    let direction: &str = if rows == cols { "diagonal" } else { "orthogonal" };
+    let cells: u64 = rows.max(cols);
+    let divisor: u64 = gcd(cells, period);
+    let (cells, period) = (cells / divisor, period / divisor);
+    match (cells, period) {
+        (1, 1) => format!("c {}", direction),
+        (1, _) => format!("c/{} {}", period, direction),
+        (_, 1) => format!("{}c {}", cells, direction),
+        _ => format!("{}c/{} {}", cells, period, direction),
+    }
+}
+
+/// Get the greatest common divisor of two numbers
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Cut a grid down to the bounding box of its live cells, which is the same wherever the pattern
+/// is, along with the top left cell of the box, or None if every cell is dead
+fn normalize(cells: &[Vec<u8>]) -> Option<(Grid, (usize, usize))> {
+    let (live_rows, live_cols) = bounds(cells)?;
+    let pattern: Grid = cells[live_rows.clone()].iter().map(|row| row[live_cols.clone()].to_vec()).collect();
+    Some((pattern, (*live_rows.start(), *live_cols.start())))
+}
+
+/// Watches the generations of a pattern for one that repeats an earlier one, possibly moved
+#[derive(Default)]
+pub struct Detector {
+    // generation and top left cell of each pattern seen, by the pattern itself so that patterns
+    // are only taken as the same when every cell is
+    seen: HashMap<Grid, (u64, (usize, usize))>,
+    seen_cells: usize,
+    generation: u64,
+    last: Option<Option<(Grid, (usize, usize))>>,
+    found: Option<Periodicity>,
+    // generation the cycle found starts at
+    cycle_start: u64,
+}
+
+impl Detector {
+    /// Record the next generation, returning how the pattern repeats once it has
+    pub fn observe(&mut self, cells: &[Vec<u8>]) -> Option<Periodicity> {
+        let normalized = normalize(cells);
+
+        // once a cycle is found, each generation must carry on with it, or else the pattern has
+        // changed, as when a spaceship hits the edge of a void world, and the search starts over
+        if let Some(found) = self.found {
+            if !self.continues(found, normalized.as_ref()) {
+                self.seen.clear();
+                self.seen_cells = 0;
+                self.found = None;
+            }
+        }
+
+        if self.found.is_none() {
+            match &normalized {
+                None => self.found = Some(Periodicity::Empty),
+                Some((pattern, (row, col))) => match self.seen.get(pattern) {
+                    Some(&(generation, (then_row, then_col))) => {
+                        self.cycle_start = generation;
+                        self.found = Some(Periodicity::Periodic {
+                            period: self.generation - generation,
+                            displacement: (*row as isize - then_row as isize, *col as isize - then_col as isize),
+                        });
+                    },
+                    None => {
+                        let size: usize = pattern.len() * pattern[0].len();
+                        if self.seen_cells + size > MAX_TRACKED_CELLS {
+                            self.seen.clear();
+                            self.seen_cells = 0;
+                        }
+                        self.seen.insert(pattern.clone(), (self.generation, (*row, *col)));
+                        self.seen_cells += size;
+                    },
+                },
+            }
+        }
+        self.last = Some(normalized);
+        self.generation += 1;
+        self.found
+    }
+
+    /// Check whether the pattern of the current generation is the one the cycle found comes to
+    fn continues(&self, found: Periodicity, normalized: Option<&(Grid, (usize, usize))>) -> bool {
+        match (found, normalized) {
+            (Periodicity::Empty, None) => true,
+            (Periodicity::Periodic { period, displacement }, Some((pattern, (row, col)))) => {
+                // the pattern is the one as far into the cycle, moved once for each time round
+                let since: u64 = self.generation - self.cycle_start;
+                let laps: isize = (since / period) as isize;
+                self.seen.get(pattern).is_some_and(|&(generation, (then_row, then_col))| {
+                    generation == self.cycle_start + since % period
+                        && *row as isize - then_row as isize == laps * displacement.0
+                        && *col as isize - then_col as isize == laps * displacement.1
+                })
+            },
+            _ => false,
+        }
+    }
+
+    /// Start over from the given cells if they are not the last generation recorded, as when
+    /// the grid has been edited since
+    pub fn sync(&mut self, cells: &[Vec<u8>]) {
+        if self.last != Some(normalize(cells)) {
+            *self = Detector::default();
+            self.observe(cells);
+        }
+    }
+
+    /// Get how the pattern repeats, if it has been seen to yet
+    pub fn found(&self) -> Option<Periodicity> {
+        self.found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary::Boundary;
+    use crate::life::{simulate, Grid};
+    use crate::rule::Rule;
+
+    /// Run the pattern until it repeats, giving up after a hundred generations
+    fn detect(mut cells: Grid) -> Option<Periodicity> {
+        let rule = Rule::default();
+        let mut detector = Detector::default();
+        detector.sync(&cells);
+        for _ in 0..100 {
+            if let Some(found) = detector.found() {
+                return Some(found);
+            }
+            cells = simulate(cells, &rule, Boundary::Void);
+            detector.observe(&cells);
+        }
+        detector.found()
+    }
+
+    /// Make a grid with the given cells alive
+    fn grid(live: &[(usize, usize)]) -> Grid {
+        let mut cells: Grid = vec![vec![0; 20]; 20];
+        for &(i, j) in live {
+            cells[i][j] = 1;
+        }
+        cells
+    }
+
+    #[test]
+    fn repeating_patterns_are_classified() {
+        let block: Grid = grid(&[(5, 5), (5, 6), (6, 5), (6, 6)]);
+        assert_eq!(detect(block), Some(Periodicity::Periodic { period: 1, displacement: (0, 0) }));
+
+        let blinker: Grid = grid(&[(5, 4), (5, 5), (5, 6)]);
+        assert_eq!(detect(blinker), Some(Periodicity::Periodic { period: 2, displacement: (0, 0) }));
+
+        let glider: Grid = grid(&[(2, 3), (3, 4), (4, 2), (4, 3), (4, 4)]);
+        let found: Option<Periodicity> = detect(glider);
+        assert_eq!(found, Some(Periodicity::Periodic { period: 4, displacement: (1, 1) }));
+        assert_eq!(found.unwrap().label(), "P4 SPACESHIP c/4 DIAGONAL");
+
+        assert_eq!(detect(grid(&[(5, 5)])), Some(Periodicity::Empty));
+    }
+
+    #[test]
+    fn patterns_that_change_are_classified_again() {
+        // a glider heading into the corner of a void world turns into a block
+        let rule = Rule::default();
+        let mut cells: Grid = grid(&[(12, 13), (13, 14), (14, 12), (14, 13), (14, 14)]);
+        let mut detector = Detector::default();
+        detector.sync(&cells);
+        let mut found: Vec<Periodicity> = Vec::new();
+        for _ in 0..100 {
+            cells = simulate(cells, &rule, Boundary::Void);
+            if let Some(periodicity) = detector.observe(&cells) {
+                if found.last() != Some(&periodicity) {
+                    found.push(periodicity);
+                }
+            }
+        }
+        assert_eq!(found, [
+            Periodicity::Periodic { period: 4, displacement: (1, 1) },
+            Periodicity::Periodic { period: 1, displacement: (0, 0) },
+        ]);
+    }
+
+    #[test]
+    fn speeds_are_written_as_usual() {
+        assert_eq!(speed(4, (0, -2)), "c/2 orthogonal");
+        assert_eq!(speed(2, (0, 1)), "c/2 orthogonal");
+        assert_eq!(speed(1, (0, 1)), "c orthogonal");
+        assert_eq!(speed(5, (0, 2)), "2c/5 orthogonal");
+        assert_eq!(speed(6, (-1, 2)), "(2,1)c/6 oblique");
+    }
+}
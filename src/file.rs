@@ -1,42 +1,136 @@
+use crate::life::Grid;
+use crate::rule::Rule;
+use crate::table::RuleTable;
+
 use rfd::FileDialog;
 
 use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+// longest line written to an RLE file
+const RLE_LINE_LENGTH: usize = 70;
+
+// number of states written with each prefix letter in multi-state RLE
+const RLE_LETTERS: u32 = 24;
 
-use crate::{SIMULATED_COLS, SIMULATED_ROWS};
+/// A pattern read from a file, along with the rule the file says it was made for, if any
+pub struct Pattern {
+    pub cells: Grid,
+    pub rule: Option<Rule>,
+}
+
+/// A file chosen to load, holding either a pattern or a rule table
+pub enum Upload {
+    Pattern(Pattern),
+    Rule(Rule),
+}
 
-/// Opens a file dialog for user to select file and get contents of file into String
-fn get_file() -> Result<String, String> {
-    let files = match FileDialog::new().pick_file() {
+/// Opens a file dialog for user to select file, starting in the given directory if any
+/// Returns the contents of the file as a String along with its path
+fn get_file(directory: Option<&Path>) -> Result<(String, PathBuf), String> {
+    let mut dialog = FileDialog::new();
+    if let Some(dir) = directory {
+        dialog = dialog.set_directory(dir);
+    }
+    let files = match dialog.pick_file() {
         Some(s) => s,
         None => return Err("No file picked".to_string()),
     };
     
-    let contents: String = match fs::read_to_string(files) {
+    let contents: String = match fs::read_to_string(&files) {
         Ok(s) => s,
         Err(_) => return Err("Invalid file picked".to_string()),
     };
 
-    Ok(contents)
+    Ok((contents, files))
 }
 
 /// Manage upload functionality for uploading a file and updating grid
-pub fn upload() -> Result<Vec<Vec<bool>>, String> {
-    let contents: String = get_file()?;
-    let new_cells: Vec<Vec<bool>> = parse_plaintext(contents)?;
-    Ok(new_cells)
+/// Returns the new pattern, or the rule if the file is a rule table starting with @RULE, along
+/// with the path of the uploaded file
+pub fn upload(
+    rows: usize,
+    cols: usize,
+    directory: Option<&Path>,
+) -> Result<(Upload, PathBuf), String> {
+    let (contents, path) = get_file(directory)?;
+    let is_rule_table: bool = contents.lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .is_some_and(|line| line.starts_with("@RULE"));
+    if is_rule_table {
+        let table: RuleTable = RuleTable::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Ok((Upload::Rule(Rule::from_table(table)), path));
+    }
+    let pattern: Pattern = parse_pattern(&contents, rows, cols)?;
+    Ok((Upload::Pattern(pattern), path))
+}
+
+/// Opens a file dialog for user to choose where to save the grid, as an RLE pattern or, if the
+/// file name ends in .cells, a plaintext pattern
+/// Returns the path that the pattern was saved to
+pub fn save(cells: &[Vec<u8>], rule: &Rule, directory: Option<&Path>) -> Result<PathBuf, String> {
+    let mut dialog = FileDialog::new()
+        .add_filter("RLE pattern", &["rle"])
+        .add_filter("Plaintext pattern", &["cells"])
+        .set_file_name("pattern.rle");
+    if let Some(dir) = directory {
+        dialog = dialog.set_directory(dir);
+    }
+    let path: PathBuf = match dialog.save_file() {
+        Some(p) => p,
+        None => return Err("No file picked".to_string()),
+    };
+
+    let name: String = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let text: String = if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("cells")) {
+        if cells.iter().flatten().any(|&state| state > 1) {
+            return Err("Plaintext patterns only hold live and dead cells, save as .rle instead".to_string());
+        }
+        to_plaintext(cells, &name)
+    } else {
+        to_rle(cells, rule, &name)
+    };
+    match fs::write(&path, text) {
+        Ok(_) => Ok(path),
+        Err(e) => Err(format!("Could not write {}: {}", path.display(), e)),
+    }
+}
+
+/// Load a pattern file from the given path into a grid of the given size
+pub fn load(path: &Path, rows: usize, cols: usize) -> Result<Pattern, String> {
+    let contents: String = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+    };
+    parse_pattern(&contents, rows, cols)
+}
+
+/// Parse a pattern in either RLE or plaintext format, telling them apart by the RLE header
+fn parse_pattern(text: &str, rows: usize, cols: usize) -> Result<Pattern, String> {
+    let is_rle: bool = text.lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with('!'))
+        .is_some_and(|line| line.starts_with('x'));
+    if is_rle {
+        parse_rle(text, rows, cols)
+    } else {
+        Ok(Pattern { cells: parse_plaintext(text, rows, cols)?, rule: None })
+    }
 }
 
 /// Parse plaintext format for a Game of Life pattern
-fn parse_plaintext(text: String) -> Result<Vec<Vec<bool>>, String> {
+fn parse_plaintext(text: &str, rows: usize, cols: usize) -> Result<Grid, String> {
     // read in lines and throw out any invalid lines
-    let mut lines: Vec<Vec<char>> = Vec::new();
+    let mut lines: Vec<Vec<u8>> = Vec::new();
     for mut line in text.split("\n") {
         line = line.trim();
         if !line.starts_with("!") && !line.is_empty() {
             if !line.chars().all(|x| x == '.' || x == 'O') {
                 return Err("Only use . or O to mark patterns".to_string());
             }
-            lines.push(line.chars().collect());
+            lines.push(line.chars().map(|x| (x == 'O') as u8).collect());
         }
     }
 
@@ -53,18 +147,282 @@ fn parse_plaintext(text: String) -> Result<Vec<Vec<bool>>, String> {
         }
     }
 
-    // get the start x and start y on a 60x60 grid if we center the pattern
-    let cells_start_x = (SIMULATED_COLS / 2) as usize - (length / 2) as usize;
-    let cells_start_y = (SIMULATED_ROWS / 2) as usize - (lines.len() / 2) as usize;
+    place(&lines, rows, cols)
+}
+
+/// Parse run length encoded format, where each state may be preceded by how many times it repeats
+/// Two-state patterns use b and o for dead and live cells, while multi-state patterns use . for
+/// state 0 and A to X for states 1 to 24, with a prefix from p to y for higher states
+fn parse_rle(text: &str, rows: usize, cols: usize) -> Result<Pattern, String> {
+    let mut width: usize = 0;
+    let mut height: usize = 0;
+    let mut rule: Option<Rule> = None;
+    let mut body = String::new();
+    let mut is_header_read = false;
 
-    // build the new grid
-    let mut cells: Vec<Vec<bool>> = vec![vec![false; SIMULATED_COLS]; SIMULATED_ROWS];
-    for i in 0..lines.len() {
-        for j in 0..length {
-            cells[i + cells_start_y][j + cells_start_x] = lines[i][j] == 'O';
+    // read the header, then gather the rest of the lines until the end of the pattern
+    for mut line in text.lines() {
+        line = line.trim();
+        if line.starts_with('#') || line.is_empty() {
+            continue;
         }
+        if !is_header_read {
+            is_header_read = true;
+            // the rule comes last and may hold commas of its own, so it takes the rest of the line
+            let (sizes, rule_field) = match line.find("rule") {
+                Some(start) => (&line[..start], Some(&line[start..])),
+                None => (line, None),
+            };
+            for field in sizes.split(',').map(str::trim).filter(|field| !field.is_empty()) {
+                let (key, value) = match field.split_once('=') {
+                    Some((key, value)) => (key.trim(), value.trim()),
+                    None => return Err(format!("Invalid RLE header field {}", field)),
+                };
+                match key {
+                    "x" => width = value.parse().map_err(|_| format!("Invalid RLE width {}", value))?,
+                    "y" => height = value.parse().map_err(|_| format!("Invalid RLE height {}", value))?,
+                    _ => {},
+                }
+            }
+            if let Some(field) = rule_field {
+                let value: &str = match field.split_once('=') {
+                    Some((_, value)) => value.trim(),
+                    None => return Err(format!("Invalid RLE header field {}", field)),
+                };
+                // anything after a colon describes a bounded world, which is ignored
+                rule = Some(Rule::parse(value.split(':').next().unwrap_or(value).trim())?);
+            }
+            continue;
+        }
+        body.push_str(line);
+        if line.contains('!') {
+            break;
+        }
+    }
+
+    // decode the runs of each state into lines of cells
+    let mut lines: Vec<Vec<u8>> = vec![Vec::new()];
+    let mut count: Option<usize> = None;
+    let mut prefix: Option<char> = None;
+    for c in body.chars() {
+        let state: u32 = match c {
+            '0'..='9' => {
+                let digit = c.to_digit(10).unwrap() as usize;
+                count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                continue;
+            },
+            'p'..='y' => {
+                prefix = Some(c);
+                continue;
+            },
+            '$' => {
+                let run: usize = count.take().unwrap_or(1);
+                if lines.len() + run > rows + 1 {
+                    return Err(format!("Pattern does not fit in a {}x{} world", rows, cols));
+                }
+                lines.resize(lines.len() + run, Vec::new());
+                continue;
+            },
+            '!' => break,
+            '.' | 'b' => 0,
+            'o' => 1,
+            'A'..='X' => {
+                let high: u32 = prefix.take().map_or(0, |p| (p as u32 - 'p' as u32 + 1) * RLE_LETTERS);
+                high + c as u32 - 'A' as u32 + 1
+            },
+            c if c.is_whitespace() => continue,
+            _ => return Err(format!("Unexpected {} in RLE pattern", c)),
+        };
+        if state > u8::MAX as u32 || rule.as_ref().is_some_and(|r| state >= r.states as u32) {
+            return Err(format!("Invalid state {} in RLE pattern", state));
+        }
+
+        let run: usize = count.take().unwrap_or(1);
+        if run > cols {
+            return Err(format!("Pattern does not fit in a {}x{} world", rows, cols));
+        }
+        let line: &mut Vec<u8> = lines.last_mut().unwrap();
+        line.extend(std::iter::repeat_n(state as u8, run));
+    }
+
+    // drop any blank lines at the end, then pad the pattern out to the size in the header
+    while lines.len() > 1 && lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    let width: usize = lines.iter().map(|line| line.len()).max().unwrap_or(0).max(width).max(1);
+    lines.resize(height.max(lines.len()), Vec::new());
+    for line in &mut lines {
+        line.resize(width, 0);
+    }
+
+    Ok(Pattern { cells: place(&lines, rows, cols)?, rule })
+}
+
+/// Place lines of cells in the middle of an otherwise dead grid of the given size
+fn place(lines: &[Vec<u8>], rows: usize, cols: usize) -> Result<Grid, String> {
+    let length = lines[0].len();
+
+    // ensure the pattern fits within the grid
+    if length > cols || lines.len() > rows {
+        return Err(format!("Pattern does not fit in a {}x{} world", rows, cols));
+    }
+
+    // get the start x and start y on the grid if we center the pattern
+    let cells_start_x = cols / 2 - length / 2;
+    let cells_start_y = rows / 2 - lines.len() / 2;
+
+    // build the new grid
+    let mut cells: Grid = vec![vec![0; cols]; rows];
+    for (i, line) in lines.iter().enumerate() {
+        cells[i + cells_start_y][cells_start_x..cells_start_x + length].copy_from_slice(line);
     }
 
     Ok(cells)
 }
 
+/// Find the rows and columns of the smallest rectangle containing every cell that is not dead
+pub fn bounds(cells: &[Vec<u8>]) -> Option<(RangeInclusive<usize>, RangeInclusive<usize>)> {
+    let live_rows: Vec<usize> = (0..cells.len()).filter(|&i| cells[i].iter().any(|&c| c != 0)).collect();
+    let live_cols: Vec<usize> = (0..cells[0].len())
+        .filter(|&j| cells.iter().any(|row| row[j] != 0))
+        .collect();
+    if live_rows.is_empty() {
+        return None;
+    }
+    Some((live_rows[0]..=live_rows[live_rows.len() - 1], live_cols[0]..=live_cols[live_cols.len() - 1]))
+}
+
+/// Write the smallest rectangle containing all live cells of the grid in plaintext format
+fn to_plaintext(cells: &[Vec<u8>], name: &str) -> String {
+    let mut text: String = format!("!Name: {}\n", name);
+    let (live_rows, live_cols) = match bounds(cells) {
+        Some(b) => b,
+        None => return text,
+    };
+
+    // write the cells within the bounding box
+    for row in &cells[live_rows] {
+        for &state in &row[live_cols.clone()] {
+            text.push(if state == 1 { 'O' } else { '.' });
+        }
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Get the letters for a state in RLE format, as used by two-state or multi-state patterns
+fn rle_letters(state: u8, is_multistate: bool) -> String {
+    match (state, is_multistate) {
+        (0, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        _ => {
+            let index = state as u32 - 1;
+            let mut letters = String::new();
+            if index >= RLE_LETTERS {
+                letters.push(char::from_u32('p' as u32 + index / RLE_LETTERS - 1).unwrap());
+            }
+            letters.push(char::from_u32('A' as u32 + index % RLE_LETTERS).unwrap());
+            letters
+        },
+    }
+}
+
+/// Write the smallest rectangle containing all live cells of the grid in RLE format
+fn to_rle(cells: &[Vec<u8>], rule: &Rule, name: &str) -> String {
+    let mut text: String = format!("#N {}\n", name);
+    let (live_rows, live_cols) = match bounds(cells) {
+        Some(b) => b,
+        None => {
+            text.push_str(&format!("x = 0, y = 0, rule = {}\n!\n", rule));
+            return text;
+        },
+    };
+    text.push_str(&format!("x = {}, y = {}, rule = {}\n",
+        live_cols.clone().count(), live_rows.clone().count(), rule));
+
+    // collect each run of a state, leaving out dead cells at the end of a line and ending each
+    // line with $, where runs of blank lines are joined into one
+    let is_multistate: bool = rule.states > 2 || cells.iter().flatten().any(|&state| state > 1);
+    let mut runs: Vec<String> = Vec::new();
+    let mut line_ends: usize = 0;
+    for row in &cells[live_rows] {
+        let row: &[u8] = &row[live_cols.clone()];
+        let length: usize = row.iter().rposition(|&state| state != 0).map_or(0, |j| j + 1);
+        if length > 0 && line_ends > 0 {
+            runs.push(if line_ends > 1 { format!("{}$", line_ends) } else { "$".to_string() });
+            line_ends = 0;
+        }
+
+        let mut j = 0;
+        while j < length {
+            let run: usize = row[j..length].iter().take_while(|&&state| state == row[j]).count();
+            let letters: String = rle_letters(row[j], is_multistate);
+            runs.push(if run > 1 { format!("{}{}", run, letters) } else { letters });
+            j += run;
+        }
+        line_ends += 1;
+    }
+    runs.push("!".to_string());
+
+    // wrap the runs into lines of limited length
+    let mut line = String::new();
+    for run in runs {
+        if line.len() + run.len() > RLE_LINE_LENGTH {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        }
+        line.push_str(&run);
+    }
+    text.push_str(&line);
+    text.push('\n');
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rle_round_trips_states() {
+        let rule: Rule = Rule::parse("B2/S345/C30").unwrap();
+        let mut cells: Grid = vec![vec![0; 9]; 8];
+        cells[2][3] = 1;
+        cells[2][4] = 1;
+        cells[2][5] = 2;
+        cells[5][4] = 29;
+
+        let text: String = to_rle(&cells, &rule, "test");
+        assert!(text.contains("2AB3$.pE!"));
+        let pattern: Pattern = parse_pattern(&text, 8, 9).unwrap();
+        assert_eq!(pattern.rule, Some(rule));
+        assert_eq!(pattern.cells, cells);
+    }
+
+    #[test]
+    fn rle_round_trips_rules_with_commas() {
+        for notation in ["R5,C0,M1,S34..58,B34..45,NM", "T52,R2", "{{{1,2,0},{0,8,0}}}"] {
+            let rule: Rule = Rule::parse(notation).unwrap();
+            let mut cells: Grid = vec![vec![0; 6]; 6];
+            cells[2][3] = 1;
+            cells[3][3] = 1;
+
+            let text: String = to_rle(&cells, &rule, "test");
+            let pattern: Pattern = parse_pattern(&text, 6, 6).unwrap();
+            assert_eq!(pattern.rule, Some(rule));
+            assert_eq!(pattern.cells, cells);
+        }
+    }
+
+    #[test]
+    fn rle_reads_two_state_patterns() {
+        let text = "#N Glider\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+        let pattern: Pattern = parse_pattern(text, 5, 5).unwrap();
+        assert_eq!(pattern.cells[1], [0, 0, 1, 0, 0]);
+        assert_eq!(pattern.cells[2], [0, 0, 0, 1, 0]);
+        assert_eq!(pattern.cells[3], [0, 1, 1, 1, 0]);
+    }
+}
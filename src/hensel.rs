@@ -0,0 +1,209 @@
+/// Offsets of the eight neighbours of a cell as (row, col), clockwise from the north
+/// Configurations of live neighbours are stored with one bit per neighbour in this order
+pub const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, 0), (-1, 1), (0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1),
+];
+
+// letters naming the configurations with each number of live neighbours, in the order Golly
+// and the LifeWiki list them
+const LETTERS: [&str; 9] = [
+    "", "ce", "cekain", "cekainyqjr", "cekainyqjrtwz", "cekainyqjr", "cekain", "ce", "",
+];
+
+// one configuration for each letter with up to four live neighbours, the rest being rotations
+// and reflections of these; configurations with more neighbours are the inverses of these
+const REPRESENTATIVES: [&[(char, u8)]; 5] = [
+    &[],
+    &[('c', 2), ('e', 1)],
+    &[('c', 10), ('e', 5), ('k', 9), ('a', 3), ('i', 17), ('n', 34)],
+    &[('c', 42), ('e', 21), ('k', 37), ('a', 7), ('i', 131),
+      ('n', 11), ('y', 41), ('q', 35), ('j', 67), ('r', 19)],
+    &[('c', 170), ('e', 85), ('k', 75), ('a', 15), ('i', 27), ('n', 139), ('y', 43),
+      ('q', 39), ('j', 83), ('r', 23), ('t', 147), ('w', 99), ('z', 51)],
+];
+
+/// Get every rotation and reflection of a configuration of live neighbours
+fn symmetries(config: u8) -> [u8; 8] {
+    // mirror the neighbours left to right, which keeps the north and south in place
+    let mut mirrored: u8 = 0;
+    for bit in 0..8 {
+        if config & 1 << bit != 0 {
+            mirrored |= 1 << ((8 - bit) % 8);
+        }
+    }
+
+    // each quarter turn moves every neighbour two places clockwise
+    let mut images = [0; 8];
+    for turn in 0..4 {
+        images[turn] = config.rotate_left(2 * turn as u32);
+        images[turn + 4] = mirrored.rotate_left(2 * turn as u32);
+    }
+    images
+}
+
+/// Get the letter of every configuration of live neighbours, or a space when there is only one
+/// configuration with its number of neighbours
+fn classify() -> [char; 256] {
+    let mut letters = [' '; 256];
+    for representatives in REPRESENTATIVES {
+        for &(letter, config) in representatives {
+            for image in symmetries(config) {
+                letters[image as usize] = letter;
+            }
+        }
+    }
+    for config in 0..256_usize {
+        if config.count_ones() > 4 {
+            letters[config] = letters[!config & 0xff];
+        }
+    }
+    letters
+}
+
+/// Parse neighbour counts in Hensel notation, such as "2-a3" or "2ae4", into a table of which
+/// configurations of live neighbours are included
+/// A count alone includes every configuration, letters after it include just those, and a minus
+/// sign before the letters includes every configuration but those
+pub fn parse(text: &str) -> Result<[bool; 256], String> {
+    let letters: [char; 256] = classify();
+    let mut table = [false; 256];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let count: u32 = match c.to_digit(10) {
+            Some(d) if d <= 8 => d,
+            _ => return Err(format!("Invalid neighbour count {} in rule", c)),
+        };
+        let is_negated: bool = chars.next_if_eq(&'-').is_some();
+        let mut chosen = String::new();
+        while let Some(letter) = chars.next_if(|l| l.is_ascii_alphabetic()) {
+            let letter: char = letter.to_ascii_lowercase();
+            if !LETTERS[count as usize].contains(letter) {
+                return Err(format!("Invalid letter {} after {} in rule", letter, count));
+            }
+            chosen.push(letter);
+        }
+        if is_negated && chosen.is_empty() {
+            return Err(format!("Expected letters after {}- in rule", count));
+        }
+
+        for config in 0..256_usize {
+            if config.count_ones() == count {
+                let is_chosen: bool = chosen.is_empty() || chosen.contains(letters[config]) != is_negated;
+                table[config] |= is_chosen;
+            }
+        }
+    }
+    Ok(table)
+}
+
+/// Write a table of configurations of live neighbours in Hensel notation, writing a count alone
+/// when all of its configurations are included and otherwise the shorter list of letters
+pub fn format(table: &[bool; 256]) -> String {
+    let letters: [char; 256] = classify();
+    let mut text = String::new();
+    for count in 0..=8 {
+        let configs: Vec<usize> = (0..256).filter(|c: &usize| c.count_ones() == count).collect();
+        let included: String = LETTERS[count as usize].chars()
+            .filter(|&l| configs.iter().any(|&c| letters[c] == l && table[c]))
+            .collect();
+        let excluded: String = LETTERS[count as usize].chars()
+            .filter(|&l| !included.contains(l))
+            .collect();
+
+        if configs.iter().all(|&c| table[c]) {
+            text.push_str(&count.to_string());
+        } else if !included.is_empty() && included.len() <= excluded.len() {
+            text.push_str(&format!("{}{}", count, included));
+        } else if !included.is_empty() {
+            text.push_str(&format!("{}-{}", count, excluded));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary::Boundary;
+    use crate::life::{simulate, Grid};
+    use crate::rule::Rule;
+
+    /// Get the configuration with live neighbours at the given (row, col) offsets
+    fn config(offsets: &[(isize, isize)]) -> usize {
+        offsets.iter().map(|offset| 1 << NEIGHBOURS.iter().position(|n| n == offset).unwrap()).sum()
+    }
+
+    #[test]
+    fn letters_match_the_published_shapes() {
+        let letters: [char; 256] = classify();
+        // an edge with a corner next to it, against two edges at right angles
+        assert_eq!(letters[config(&[(-1, 0), (-1, 1)])], 'a');
+        assert_eq!(letters[config(&[(-1, 0), (0, 1)])], 'e');
+        // two corners on one side, opposite edges, opposite corners, and an edge with a far corner
+        assert_eq!(letters[config(&[(-1, -1), (-1, 1)])], 'c');
+        assert_eq!(letters[config(&[(-1, 0), (1, 0)])], 'i');
+        assert_eq!(letters[config(&[(-1, -1), (1, 1)])], 'n');
+        assert_eq!(letters[config(&[(-1, 0), (1, 1)])], 'k');
+        // a whole side, and the two corners of a side with the edge opposite
+        assert_eq!(letters[config(&[(-1, -1), (-1, 0), (-1, 1)])], 'i');
+        assert_eq!(letters[config(&[(-1, -1), (-1, 1), (1, 0)])], 'y');
+        // a whole side with the edge opposite, a staircase, and a z
+        assert_eq!(letters[config(&[(-1, -1), (-1, 0), (-1, 1), (1, 0)])], 't');
+        assert_eq!(letters[config(&[(-1, -1), (0, -1), (1, 0), (1, 1)])], 'w');
+        assert_eq!(letters[config(&[(-1, -1), (-1, 0), (1, 0), (1, 1)])], 'z');
+    }
+
+    #[test]
+    fn non_totalistic_rules_evolve_as_written() {
+        let rule: Rule = Rule::parse("B2-a/S12").unwrap();
+        let grid = |live: &[(usize, usize)]| -> Grid {
+            let mut cells: Grid = vec![vec![0; 6]; 6];
+            for &(i, j) in live {
+                cells[i][j] = 1;
+            }
+            cells
+        };
+
+        // a domino gives every dead cell next to it an edge and a corner, 2a, so nothing is born
+        let domino: Grid = grid(&[(2, 2), (2, 3)]);
+        assert_eq!(simulate(domino.clone(), &rule, Boundary::Void), domino);
+
+        // two cells a space apart give birth between them (2i) and above and below (2c), and die
+        let pair: Grid = grid(&[(2, 1), (2, 3)]);
+        assert_eq!(simulate(pair, &rule, Boundary::Void), grid(&[(1, 2), (2, 2), (3, 2)]));
+    }
+
+    #[test]
+    fn letters_cover_every_configuration() {
+        let letters: [char; 256] = classify();
+        for (config, &letter) in letters.iter().enumerate() {
+            let count = config.count_ones() as usize;
+            assert_eq!(LETTERS[count].is_empty(), letter == ' ', "configuration {}", config);
+        }
+
+        // the configurations of each letter are exactly the symmetries of its representative
+        for representatives in REPRESENTATIVES {
+            for &(letter, config) in representatives {
+                let class: Vec<usize> = (0..256_usize)
+                    .filter(|&c| c.count_ones() == config.count_ones() && letters[c] == letter)
+                    .collect();
+                let mut images: Vec<usize> = symmetries(config).iter().map(|&c| c as usize).collect();
+                images.sort();
+                images.dedup();
+                assert_eq!(class, images, "letter {}{}", config.count_ones(), letter);
+            }
+        }
+    }
+
+    #[test]
+    fn notation_round_trips() {
+        for text in ["2-a3", "2ce3aijr", "3", "012345678", "4twz", "1c2-n5-ky"] {
+            assert_eq!(format(&parse(text).unwrap()), text);
+        }
+
+        // letters are written in a fixed order, whatever order they were given in
+        assert_eq!(format(&parse("2ae").unwrap()), "2ea");
+        assert!(parse("2x").is_err());
+        assert!(parse("3-").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+
+use sdl2::keyboard::{Keycode, Mod};
+
+use std::collections::BTreeMap;
+
+/// Actions that can be triggered from the keyboard or from the controls
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    PlayPause,
+    Step,
+    Clear,
+    Randomize,
+    NextFillRegion,
+    NextUpdateMode,
+    ClearSelection,
+    ZoomIn,
+    ZoomOut,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    Undo,
+    Redo,
+    Save,
+    Load,
+    #[serde(alias = "toggle_boundary")]
+    NextBoundary,
+    NextRule,
+    NextTheme,
+    NextGridLines,
+    NextView,
+    ResizeWorld,
+    TrackWindow,
+    AddAnt,
+    FindPredecessor,
+    Help,
+}
+
+/// Every action, in the order they are listed in the help overlay
+pub const ACTIONS: [Action; 27] = [
+    Action::PlayPause,
+    Action::Step,
+    Action::Clear,
+    Action::Randomize,
+    Action::NextFillRegion,
+    Action::NextUpdateMode,
+    Action::ClearSelection,
+    Action::ZoomIn,
+    Action::ZoomOut,
+    Action::PanUp,
+    Action::PanDown,
+    Action::PanLeft,
+    Action::PanRight,
+    Action::Undo,
+    Action::Redo,
+    Action::Save,
+    Action::Load,
+    Action::NextBoundary,
+    Action::NextRule,
+    Action::NextTheme,
+    Action::NextGridLines,
+    Action::NextView,
+    Action::ResizeWorld,
+    Action::TrackWindow,
+    Action::AddAnt,
+    Action::FindPredecessor,
+    Action::Help,
+];
+
+impl Action {
+    /// Get a short description of the action for the help overlay
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::PlayPause => "Play / pause",
+            Action::Step => "Step one generation",
+            Action::Clear => "Clear grid",
+            Action::Randomize => "Random fill",
+            Action::NextFillRegion => "Cycle fill region",
+            Action::NextUpdateMode => "Cycle update mode",
+            Action::ClearSelection => "Clear selection",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::PanUp => "Pan up",
+            Action::PanDown => "Pan down",
+            Action::PanLeft => "Pan left",
+            Action::PanRight => "Pan right",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Save => "Save pattern",
+            Action::Load => "Load pattern",
+            Action::NextBoundary => "Cycle boundary",
+            Action::NextRule => "Next rule",
+            Action::NextTheme => "Next theme",
+            Action::NextGridLines => "Cycle grid lines",
+            Action::NextView => "Cycle view mode",
+            Action::ResizeWorld => "Resize world",
+            Action::TrackWindow => "Fit world to window",
+            Action::AddAnt => "Add ant under cursor",
+            Action::FindPredecessor => "Find predecessor",
+            Action::Help => "Show / hide this help",
+        }
+    }
+
+    /// Get the keys bound to the action when the config does not say otherwise
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::PlayPause => &["Space"],
+            Action::Step => &["N"],
+            Action::Clear => &["Delete"],
+            Action::Randomize => &["R"],
+            Action::NextFillRegion => &["F"],
+            Action::NextUpdateMode => &["U"],
+            Action::ClearSelection => &["Escape"],
+            Action::ZoomIn => &["=", "Keypad +"],
+            Action::ZoomOut => &["-", "Keypad -"],
+            Action::PanUp => &["Up"],
+            Action::PanDown => &["Down"],
+            Action::PanLeft => &["Left"],
+            Action::PanRight => &["Right"],
+            Action::Undo => &["Ctrl+Z"],
+            Action::Redo => &["Ctrl+Y", "Ctrl+Shift+Z"],
+            Action::Save => &["Ctrl+S"],
+            Action::Load => &["Ctrl+O"],
+            Action::NextBoundary => &["B"],
+            Action::NextRule => &["L"],
+            Action::NextTheme => &["T"],
+            Action::NextGridLines => &["G"],
+            Action::NextView => &["V"],
+            Action::ResizeWorld => &["W"],
+            Action::TrackWindow => &["Shift+W"],
+            Action::AddAnt => &["A"],
+            Action::FindPredecessor => &["P"],
+            Action::Help => &["H", "F1"],
+        }
+    }
+}
+
+/// Get the default key bindings, as stored in the config file
+pub fn default_bindings() -> BTreeMap<Action, Vec<String>> {
+    ACTIONS
+        .iter()
+        .map(|&action| (action, action.default_keys().iter().map(|k| k.to_string()).collect()))
+        .collect()
+}
+
+/// A key along with the modifiers that must be held with it
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct KeyBinding {
+    keycode: Keycode,
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+}
+
+impl KeyBinding {
+    /// Parse a key binding of the form Ctrl+Shift+Z, where the key is an SDL key name
+    fn parse(text: &str) -> Result<KeyBinding, String> {
+        let (mut ctrl, mut shift, mut alt) = (false, false, false);
+
+        // the key itself may be "+", so only split modifiers off the front
+        let mut rest: &str = text.trim();
+        while let Some((modifier, key)) = rest.split_once('+').filter(|(_, key)| !key.is_empty()) {
+            match modifier.trim().to_lowercase().as_str() {
+                "ctrl" | "control" => ctrl = true,
+                "shift" => shift = true,
+                "alt" => alt = true,
+                _ => break,
+            }
+            rest = key;
+        }
+
+        match Keycode::from_name(rest.trim()) {
+            Some(keycode) => Ok(KeyBinding { keycode, ctrl, shift, alt }),
+            None => Err(format!("Unknown key {}", text)),
+        }
+    }
+
+    /// Check whether a key press with the given modifiers triggers this binding
+    fn matches(&self, keycode: Keycode, keymod: Mod) -> bool {
+        self.keycode == keycode
+            && self.ctrl == keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD | Mod::LGUIMOD | Mod::RGUIMOD)
+            && self.shift == keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD)
+            && self.alt == keymod.intersects(Mod::LALTMOD | Mod::RALTMOD)
+    }
+}
+
+/// Mapping from keys to actions
+pub struct Keymap {
+    bindings: Vec<(KeyBinding, Action)>,
+    names: BTreeMap<Action, Vec<String>>,
+}
+
+impl Keymap {
+    /// Build the keymap from the bindings in the config, using the defaults for missing actions
+    pub fn new(config_bindings: &BTreeMap<Action, Vec<String>>) -> Keymap {
+        let mut names: BTreeMap<Action, Vec<String>> = default_bindings();
+        for (action, keys) in config_bindings {
+            names.insert(*action, keys.clone());
+        }
+
+        let mut bindings: Vec<(KeyBinding, Action)> = Vec::new();
+        for (action, keys) in &mut names {
+            keys.retain(|key| match KeyBinding::parse(key) {
+                Ok(binding) => {
+                    bindings.push((binding, *action));
+                    true
+                },
+                Err(e) => {
+                    eprintln!("WARNING: Ignoring key binding for {}: {}", action.label(), e);
+                    false
+                },
+            });
+        }
+
+        Keymap { bindings, names }
+    }
+
+    /// Get the action bound to a key press with the given modifiers, if any
+    pub fn action(&self, keycode: Keycode, keymod: Mod) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| binding.matches(keycode, keymod))
+            .map(|(_, action)| *action)
+    }
+
+    /// Get the names of the keys bound to an action, separated by commas
+    pub fn keys(&self, action: Action) -> String {
+        self.names[&action].join(", ")
+    }
+
+    /// Get a line for each action describing it and the keys bound to it, for the help overlay
+    pub fn describe(&self) -> Vec<(&'static str, String)> {
+        ACTIONS
+            .iter()
+            .map(|&action| (action.label(), self.keys(action)))
+            .collect()
+    }
+}
@@ -1,97 +1,937 @@
-/// Apply the rules of the game of life with a vector slice to the center cell
-fn apply_rules(cells: &Vec<Vec<bool>>) -> bool {
-    // count number of adjacent cells that are alive
-    let mut count = 0;
-    if cells[0][0] { count += 1; }
-    if cells[0][1] { count += 1; }
-    if cells[0][2] { count += 1; }
-    if cells[1][0] { count += 1; }
-    if cells[1][2] { count += 1; }
-    if cells[2][0] { count += 1; }
-    if cells[2][1] { count += 1; }
-    if cells[2][2] { count += 1; }
-    
-    if cells[1][1] {
-        // rule of underpopulation
-        if count < 2 { return false; }
-        // rule of overpopulation
-        else if count > 3 { return false; }
-        // otherwise lives
-        else { return true; }
-    } else {
-        // rule of reproduction
-        if count == 3 { return true; }
-        // otherwise stays dead
-        else { return false; }
+use crate::boundary::Boundary;
+use crate::hensel::NEIGHBOURS;
+use crate::rule::{LargerThanLife, Neighbourhood, Rule, Wolfram};
+use crate::table::RuleTable;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use serde::{Deserialize, Serialize};
+
+/// Grid of cell states, indexed by row then column
+/// State 0 is dead and state 1 is alive; higher states are only used by rules with more states
+pub type Grid = Vec<Vec<u8>>;
+
+/// Apply the given rule with a vector slice to the center cell
+fn apply_rules(cells: &Vec<Vec<u8>>, rule: &Rule) -> u8 {
+    // note which adjacent cells are alive, ignoring any decaying cells
+    let mut neighbours: u8 = 0;
+    for (bit, (di, dj)) in NEIGHBOURS.iter().enumerate() {
+        if cells[(1 + di) as usize][(1 + dj) as usize] == 1 {
+            neighbours |= 1 << bit;
+        }
     }
+
+    rule.next_state(cells[1][1], neighbours)
 }
 
 /// Simulates one generation of the game, returning the updated grid
-pub fn simulate(cells: Vec<Vec<bool>>, is_wrap: bool) -> Vec<Vec<bool>> {
+pub fn simulate(cells: Grid, rule: &Rule, boundary: Boundary) -> Grid {
+    if let Some(ltl) = &rule.ltl {
+        return simulate_ltl(&cells, rule, ltl, boundary);
+    }
+    if let Some(table) = &rule.table {
+        return simulate_table(&cells, table, boundary);
+    }
+
     // the new vector of cells to return, representing the next generation
     let rows = cells.len();
     let cols = cells[0].len();
-    let mut ret_cells: Vec<Vec<bool>> = vec![vec![false; cols]; rows];
+    let mut ret_cells: Grid = vec![vec![0; cols]; rows];
+
+    // note which rows have any live or decaying cells, to skip rows with nothing around them
+    let is_row_alive: Vec<bool> = cells.iter().map(|row| row.iter().any(|&c| c != 0)).collect();
 
     // iterate through cells and apply the rules
     for i in 0..rows {
         // if the previous row, this row, and the next row are all false, can skip
-        if i > 0 && i < rows - 1 {
-            let mut all_false = true;
-            for row in i-1..=i+1 {
-                for j in 0..cols {
-                    if cells[row][j] {
-                        all_false = false;
-                        break;
-                    }
+        // unless the rule gives birth to cells with no neighbours, or the boundary joins the
+        // sides of the row to other rows
+        // the rows next to the first and last rows are found through the boundary
+        if !rule.is_born_alone() && !boundary.crosses_rows() {
+            let all_false: bool = (-1..=1).all(|di| {
+                match boundary.resolve(i as isize + di, 0, rows, cols) {
+                    Some((row, _)) => !is_row_alive[row],
+                    None => true,
                 }
-                if !all_false { break; }
-            }
+            });
             if all_false { continue; }
         }
         
         for j in 0..cols {
             // slice to pass in for rule application
-            let mut slice: Vec<Vec<bool>> = vec![vec![false; 3]; 3];
+            let mut slice: Vec<Vec<u8>> = vec![vec![0; 3]; 3];
             let mut is_zero = true;
 
-            if is_wrap {
-                // utilize wrap-around coordinates and fill the slice 
+            let is_edge: bool = i == 0 || i == rows - 1 || j == 0 || j == cols - 1;
+            if is_edge && boundary == Boundary::Void {
+                // make cells disappear after they go past boundary
+                ret_cells[i][j] = 0;
+                continue;
+            } else if is_edge {
+                // find the neighbours beyond the edge through the boundary and fill the slice
                 for di in 0..3 {
                     for dj in 0..3 {
-                        let row = (i + rows - 1 + di) % rows;
-                        let col = (j + cols - 1 + dj) % cols;
-                        slice[di][dj] = cells[row][col];
-                        if cells[row][col] { is_zero = false; }
+                        let row = i as isize + di as isize - 1;
+                        let col = j as isize + dj as isize - 1;
+                        if let Some((row, col)) = boundary.resolve(row, col, rows, cols) {
+                            slice[di][dj] = cells[row][col];
+                            if cells[row][col] != 0 { is_zero = false; }
+                        }
                     }
                 }
             } else {
-                // make cells disappear after they go past boundary
-                if i == 0 || i == rows - 1 || j == 0 || j == rows - 1 {
-                    ret_cells[i][j] = false;
-                    continue;
-                } else {
-                    for di in 0..3 {
-                        for dj in 0..3 {
-                            let row = i + di - 1;
-                            let col = j + dj - 1;
-                            slice[di][dj] = cells[row][col];
-                            if cells[row][col] { is_zero = false; }
-                        }
+                for di in 0..3 {
+                    for dj in 0..3 {
+                        let row = i + di - 1;
+                        let col = j + dj - 1;
+                        slice[di][dj] = cells[row][col];
+                        if cells[row][col] != 0 { is_zero = false; }
                     }
                 }
             }
 
             // if it is all zeroes, then no need to apply rules
-            if is_zero {
-                ret_cells[i][j] = false;
+            if is_zero && !rule.is_born_alone() {
+                ret_cells[i][j] = 0;
                 continue;
             }
 
             // apply the rules
-            ret_cells[i][j] = apply_rules(&slice);
+            ret_cells[i][j] = apply_rules(&slice, rule);
         }
     }
     
     ret_cells
 }
+
+/// Simulates one generation of a Larger than Life rule, returning the updated grid
+/// Each neighbourhood is counted from a summed-area table of the live cells, which is padded by
+/// the radius on every side with the cells found through the boundary
+fn simulate_ltl(cells: &[Vec<u8>], rule: &Rule, ltl: &LargerThanLife, boundary: Boundary) -> Grid {
+    let rows = cells.len();
+    let cols = cells[0].len();
+    let radius = ltl.radius;
+
+    // sums[i][j] holds the number of live cells in the padded grid above and left of (i, j)
+    let mut sums: Vec<Vec<usize>> = vec![vec![0; cols + 2 * radius + 1]; rows + 2 * radius + 1];
+    for i in 0..rows + 2 * radius {
+        for j in 0..cols + 2 * radius {
+            let row = i as isize - radius as isize;
+            let col = j as isize - radius as isize;
+            let is_alive: bool = boundary.resolve(row, col, rows, cols)
+                .is_some_and(|(row, col)| cells[row][col] == 1);
+            sums[i + 1][j + 1] = sums[i][j + 1] + sums[i + 1][j] - sums[i][j] + is_alive as usize;
+        }
+    }
+
+    // count the live cells in the padded rows and columns top..bottom and left..right
+    let count_in = |top: usize, bottom: usize, left: usize, right: usize| -> usize {
+        sums[bottom][right] + sums[top][left] - sums[top][right] - sums[bottom][left]
+    };
+
+    let mut ret_cells: Grid = vec![vec![0; cols]; rows];
+    for (i, row) in ret_cells.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            // make cells disappear after they go past boundary
+            let is_edge: bool = i == 0 || i == rows - 1 || j == 0 || j == cols - 1;
+            if is_edge && boundary == Boundary::Void {
+                continue;
+            }
+
+            // the cell sits at (i + radius, j + radius) in the padded grid
+            let mut count: usize = match ltl.neighbourhood {
+                Neighbourhood::Moore => count_in(i, i + 2 * radius + 1, j, j + 2 * radius + 1),
+                // Larger than Life notation only has the NM, NN and NH neighbourhoods
+                Neighbourhood::Custom(_) => unreachable!("Larger than Life rules have no custom neighbourhoods"),
+                // add up the span of the neighbourhood in each row
+                Neighbourhood::VonNeumann | Neighbourhood::Hexagonal => (-(radius as isize)..=radius as isize).map(|di| {
+                    let (left, right) = ltl.neighbourhood.row_span(radius, di);
+                    let row: usize = (i + radius).wrapping_add_signed(di);
+                    count_in(row, row + 1, (j + radius).wrapping_add_signed(left), (j + radius).wrapping_add_signed(right) + 1)
+                }).sum(),
+            };
+            if !ltl.is_center_counted && cells[i][j] == 1 {
+                count -= 1;
+            }
+            *cell = rule.next_state_counted(ltl, cells[i][j], count);
+        }
+    }
+    ret_cells
+}
+
+/// Simulates one generation of a rule table, returning the updated grid
+fn simulate_table(cells: &[Vec<u8>], table: &RuleTable, boundary: Boundary) -> Grid {
+    let rows = cells.len();
+    let cols = cells[0].len();
+    let offsets: Vec<(isize, isize)> = table.offsets();
+
+    let mut ret_cells: Grid = vec![vec![0; cols]; rows];
+    for (i, row) in ret_cells.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            // make cells disappear after they go past boundary
+            let is_edge: bool = i == 0 || i == rows - 1 || j == 0 || j == cols - 1;
+            if is_edge && boundary == Boundary::Void {
+                continue;
+            }
+
+            // the state of the cell followed by those of its neighbours in the table's order
+            let mut states: Vec<u8> = vec![cells[i][j]];
+            states.extend(offsets.iter().map(|(di, dj)| {
+                boundary.resolve(i as isize + di, j as isize + dj, rows, cols)
+                    .map_or(0, |(row, col)| cells[row][col])
+            }));
+            *cell = table.next_state(&states);
+        }
+    }
+    ret_cells
+}
+
+/// Simulates one generation of a one-dimensional rule from the given row of a space-time diagram,
+/// drawing it on the next row, or on the last row after scrolling the diagram up if the given row
+/// is the last, and returns the row it was drawn on
+/// The ends of the row are joined as the boundary joins the left and right edges of a world that
+/// is one row tall
+pub fn simulate_line(cells: &mut Grid, row: usize, wolfram: &Wolfram, boundary: Boundary) -> usize {
+    let cols = cells[0].len();
+    let radius = wolfram.radius as isize;
+    let line: Vec<u8> = (0..cols as isize).map(|j| {
+        let states: Vec<u8> = (j - radius..=j + radius)
+            .map(|col| boundary.resolve(0, col, 1, cols).map_or(0, |(_, col)| cells[row][col]))
+            .collect();
+        wolfram.next_state(&states)
+    }).collect();
+
+    if row + 1 < cells.len() {
+        cells[row + 1] = line;
+        row + 1
+    } else {
+        cells.remove(0);
+        cells.push(line);
+        row
+    }
+}
+
+/// Find the next state of a single cell under a rule on the grid, finding every neighbour through
+/// the boundary, which is slower than simulating the whole grid at once but lets cells be
+/// updated one at a time
+fn next_state_at(cells: &[Vec<u8>], i: usize, j: usize, rule: &Rule, boundary: Boundary) -> u8 {
+    let rows = cells.len();
+    let cols = cells[0].len();
+    let (row, col) = (i as isize, j as isize);
+    let state_at = |di: isize, dj: isize| -> u8 {
+        boundary.resolve(row + di, col + dj, rows, cols).map_or(0, |(row, col)| cells[row][col])
+    };
+
+    match (&rule.ltl, &rule.table) {
+        (Some(ltl), _) => {
+            let radius = ltl.radius as isize;
+            let mut count = 0;
+            for di in -radius..=radius {
+                let (left, right) = ltl.neighbourhood.row_span(ltl.radius, di);
+                for dj in left..=right {
+                    if di == 0 && dj == 0 && !ltl.is_center_counted {
+                        continue;
+                    }
+                    count += (state_at(di, dj) == 1) as usize;
+                }
+            }
+            rule.next_state_counted(ltl, cells[i][j], count)
+        },
+        (None, Some(table)) => {
+            let mut states: Vec<u8> = vec![cells[i][j]];
+            states.extend(table.offsets().iter().map(|&(di, dj)| state_at(di, dj)));
+            table.next_state(&states)
+        },
+        (None, None) => {
+            let mut neighbours: u8 = 0;
+            for (bit, &(di, dj)) in NEIGHBOURS.iter().enumerate() {
+                neighbours |= ((state_at(di, dj) == 1) as u8) << bit;
+            }
+            rule.next_state(cells[i][j], neighbours)
+        },
+    }
+}
+
+/// How the cells of the world are updated each generation
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateMode {
+    /// Every cell is updated at once from the previous generation
+    #[serde(alias = "sync")]
+    Synchronous,
+    /// Every cell is updated at once, but each only with some probability, keeping its state
+    /// otherwise
+    Stochastic,
+    /// Cells are updated one at a time in a random order, each seeing the cells updated before it
+    #[serde(alias = "async")]
+    Asynchronous,
+}
+
+impl UpdateMode {
+    /// Find an update mode by name, ignoring case, also accepting sync and async
+    pub fn from_name(name: &str) -> Option<UpdateMode> {
+        match name.to_lowercase().as_str() {
+            "synchronous" | "sync" => Some(UpdateMode::Synchronous),
+            "stochastic" => Some(UpdateMode::Stochastic),
+            "asynchronous" | "async" => Some(UpdateMode::Asynchronous),
+            _ => None,
+        }
+    }
+
+    /// Get the next option when cycling through update modes
+    pub fn next(&self) -> UpdateMode {
+        match self {
+            UpdateMode::Synchronous => UpdateMode::Stochastic,
+            UpdateMode::Stochastic => UpdateMode::Asynchronous,
+            UpdateMode::Asynchronous => UpdateMode::Synchronous,
+        }
+    }
+
+    /// Get the text shown in the status label for this option
+    pub fn label(&self) -> &'static str {
+        match self {
+            UpdateMode::Synchronous => "SYNC",
+            UpdateMode::Stochastic => "STOCHASTIC",
+            UpdateMode::Asynchronous => "ASYNC",
+        }
+    }
+}
+
+/// Simulates one generation under the given update mode, where stochastic updates change each
+/// cell with the given probability, then flips cells between dead and alive at the noise rate
+/// All randomness comes from the given generator, so a seeded generator repeats the same run
+/// Cells on the edges of a void world stay dead, as they do in synchronous updates
+pub fn simulate_randomly(
+    cells: Grid,
+    rule: &Rule,
+    boundary: Boundary,
+    mode: UpdateMode,
+    probability: f64,
+    noise: f64,
+    rng: &mut impl Rng,
+) -> Grid {
+    let rows = cells.len();
+    let cols = cells[0].len();
+    let is_kept_dead = |i: usize, j: usize| -> bool {
+        boundary == Boundary::Void && (i == 0 || i == rows - 1 || j == 0 || j == cols - 1)
+    };
+
+    let mut cells: Grid = match mode {
+        UpdateMode::Synchronous => simulate(cells, rule, boundary),
+        UpdateMode::Stochastic => {
+            let mut next: Grid = simulate(cells.clone(), rule, boundary);
+            for (i, row) in next.iter_mut().enumerate() {
+                for (j, cell) in row.iter_mut().enumerate() {
+                    if !is_kept_dead(i, j) && !rng.gen_bool(probability) {
+                        *cell = cells[i][j];
+                    }
+                }
+            }
+            next
+        },
+        UpdateMode::Asynchronous => {
+            let mut cells = cells;
+            let mut order: Vec<(usize, usize)> = (0..rows).flat_map(|i| (0..cols).map(move |j| (i, j))).collect();
+            order.shuffle(rng);
+            for (i, j) in order {
+                cells[i][j] = if is_kept_dead(i, j) { 0 } else { next_state_at(&cells, i, j, rule, boundary) };
+            }
+            cells
+        },
+    };
+
+    if noise > 0.0 {
+        for (i, row) in cells.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                if !is_kept_dead(i, j) && rng.gen_bool(noise) {
+                    *cell = (*cell == 0) as u8;
+                }
+            }
+        }
+    }
+    cells
+}
+
+/// Rectangle of backend cells, given by its top left cell and its size
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Region {
+    pub row: usize,
+    pub col: usize,
+    pub rows: usize,
+    pub cols: usize,
+}
+
+impl Region {
+    /// Get the region covering a whole grid of the given size
+    pub fn whole(rows: usize, cols: usize) -> Region {
+        Region { row: 0, col: 0, rows, cols }
+    }
+
+    /// Get a square region of the given size centred in a grid, shrunk to fit if needed
+    pub fn centred(size: usize, rows: usize, cols: usize) -> Region {
+        let (box_rows, box_cols) = (size.min(rows), size.min(cols));
+        Region { row: (rows - box_rows) / 2, col: (cols - box_cols) / 2, rows: box_rows, cols: box_cols }
+    }
+
+    /// Get the region spanning two (row, col) corner cells, in either order
+    pub fn between(a: (usize, usize), b: (usize, usize)) -> Region {
+        Region {
+            row: a.0.min(b.0),
+            col: a.1.min(b.1),
+            rows: a.0.abs_diff(b.0) + 1,
+            cols: a.1.abs_diff(b.1) + 1,
+        }
+    }
+
+    /// Get the part of the region within a grid of the given size, or None if none of it is
+    pub fn clip(&self, rows: usize, cols: usize) -> Option<Region> {
+        if self.row >= rows || self.col >= cols || self.rows == 0 || self.cols == 0 {
+            return None;
+        }
+        Some(Region {
+            row: self.row,
+            col: self.col,
+            rows: self.rows.min(rows - self.row),
+            cols: self.cols.min(cols - self.col),
+        })
+    }
+}
+
+/// Which part of the grid is filled when making a random soup
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FillRegion {
+    Whole,
+    Selection,
+    Box,
+}
+
+impl FillRegion {
+    /// Get the next option when cycling through fill regions
+    pub fn next(&self) -> FillRegion {
+        match self {
+            FillRegion::Whole => FillRegion::Selection,
+            FillRegion::Selection => FillRegion::Box,
+            FillRegion::Box => FillRegion::Whole,
+        }
+    }
+
+    /// Get the text shown in the status label for this option
+    pub fn label(&self) -> &'static str {
+        match self {
+            FillRegion::Whole => "ALL",
+            FillRegion::Selection => "SELECTION",
+            FillRegion::Box => "BOX",
+        }
+    }
+}
+
+/// Clear any cells in states beyond those of a rule with the given number of states, after the
+/// rule has changed
+pub fn clear_extra_states(cells: &mut [Vec<u8>], states: u8) {
+    for cell in cells.iter_mut().flatten() {
+        if *cell >= states {
+            *cell = 0;
+        }
+    }
+}
+
+/// Fill a region of the grid randomly, where each cell is alive with the given probability
+/// Cells outside of the region are left as they are, and any of the region beyond the grid is
+/// ignored
+pub fn random_fill(cells: &mut [Vec<u8>], region: Region, density: f64, rng: &mut impl Rng) {
+    let region: Region = match region.clip(cells.len(), cells.first().map_or(0, |row| row.len())) {
+        Some(region) => region,
+        None => return,
+    };
+    for row in &mut cells[region.row..region.row + region.rows] {
+        for cell in &mut row[region.col..region.col + region.cols] {
+            *cell = rng.gen_bool(density) as u8;
+        }
+    }
+}
+
+/// Which part of the pattern stays in place when the world is resized
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Anchor {
+    Center,
+    #[serde(rename = "topleft")]
+    TopLeft,
+}
+
+impl Anchor {
+    /// Get the next option when cycling through anchors
+    pub fn next(&self) -> Anchor {
+        match self {
+            Anchor::Center => Anchor::TopLeft,
+            Anchor::TopLeft => Anchor::Center,
+        }
+    }
+
+    /// Get the text shown in the resize dialog for this option
+    pub fn label(&self) -> &'static str {
+        match self {
+            Anchor::Center => "CENTER",
+            Anchor::TopLeft => "TOP LEFT",
+        }
+    }
+}
+
+/// Copy the grid into a world of the given size, keeping the anchored part of the pattern in
+/// place; cells that no longer fit are dropped, and new cells are dead
+pub fn resize(cells: &[Vec<u8>], rows: usize, cols: usize, anchor: Anchor) -> Grid {
+    let old_rows = cells.len() as isize;
+    let old_cols = cells[0].len() as isize;
+    let (row_offset, col_offset) = match anchor {
+        Anchor::Center => ((rows as isize - old_rows) / 2, (cols as isize - old_cols) / 2),
+        Anchor::TopLeft => (0, 0),
+    };
+
+    let mut resized: Grid = vec![vec![0; cols]; rows];
+    for (i, row) in resized.iter_mut().enumerate() {
+        let old_i: isize = i as isize - row_offset;
+        if !(0..old_rows).contains(&old_i) {
+            continue;
+        }
+        for (j, cell) in row.iter_mut().enumerate() {
+            let old_j: isize = j as isize - col_offset;
+            if (0..old_cols).contains(&old_j) {
+                *cell = cells[old_i as usize][old_j as usize];
+            }
+        }
+    }
+    resized
+}
+
+/// Per-cell history of the grid, used to visualise how long cells have been alive or dead
+/// Decaying cells count as alive, as they are still visible
+pub struct History {
+    /// Number of consecutive generations each cell has been alive, 0 if dead
+    pub age: Vec<Vec<u32>>,
+    /// Number of generations since each cell was last alive, 0 if alive, u32::MAX if never alive
+    pub dead_for: Vec<Vec<u32>>,
+}
+
+impl History {
+    /// Start tracking history from the given grid
+    pub fn new(cells: &[Vec<u8>]) -> Self {
+        let rows = cells.len();
+        let cols = cells[0].len();
+        let mut history = History {
+            age: vec![vec![0; cols]; rows],
+            dead_for: vec![vec![u32::MAX; cols]; rows],
+        };
+        history.record(cells);
+        history
+    }
+
+    /// Record the next generation of the grid
+    pub fn record(&mut self, cells: &[Vec<u8>]) {
+        for (i, row) in cells.iter().enumerate() {
+            for (j, &state) in row.iter().enumerate() {
+                self.set(i, j, state != 0);
+            }
+        }
+    }
+
+    /// Record the state of a single cell for the next generation
+    pub fn set(&mut self, row: usize, col: usize, is_alive: bool) {
+        if is_alive {
+            self.age[row][col] = self.age[row][col].saturating_add(1);
+            self.dead_for[row][col] = 0;
+        } else {
+            self.age[row][col] = 0;
+            self.dead_for[row][col] = self.dead_for[row][col].saturating_add(1);
+        }
+    }
+
+    /// Check whether a cell has ever been alive since history started
+    pub fn ever_alive(&self, row: usize, col: usize) -> bool {
+        self.dead_for[row][col] != u32::MAX
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary::BOUNDARIES;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // number of random worlds to check, and generations to run each of them for
+    const CASES: u64 = 500;
+    const GENERATIONS: usize = 8;
+
+    // largest number of rows or columns of a random world
+    const MAX_SIZE: usize = 12;
+
+    /// Compute the next generation cell by cell, finding every neighbour through the boundary
+    /// and counting them independently of the engine
+    fn reference(cells: &[Vec<u8>], rule: &Rule, boundary: Boundary) -> Grid {
+        let rows = cells.len();
+        let cols = cells[0].len();
+        let is_alive = |row: isize, col: isize| -> bool {
+            boundary.resolve(row, col, rows, cols).is_some_and(|(row, col)| cells[row][col] == 1)
+        };
+
+        let mut next: Grid = vec![vec![0; cols]; rows];
+        for (i, next_row) in next.iter_mut().enumerate() {
+            for (j, cell) in next_row.iter_mut().enumerate() {
+                let is_edge: bool = i == 0 || i == rows - 1 || j == 0 || j == cols - 1;
+                if boundary == Boundary::Void && is_edge {
+                    continue;
+                }
+                let (row, col) = (i as isize, j as isize);
+
+                *cell = match (&rule.ltl, &rule.table) {
+                    (Some(ltl), _) => {
+                        let radius = ltl.radius as isize;
+                        let mut count = 0;
+                        for di in -radius..=radius {
+                            for dj in -radius..=radius {
+                                if di == 0 && dj == 0 && !ltl.is_center_counted {
+                                    continue;
+                                }
+                                let (left, right) = ltl.neighbourhood.row_span(ltl.radius, di);
+                                if dj < left || dj > right {
+                                    continue;
+                                }
+                                count += is_alive(row + di, col + dj) as usize;
+                            }
+                        }
+                        rule.next_state_counted(ltl, cells[i][j], count)
+                    },
+                    (None, Some(table)) => {
+                        let mut states: Vec<u8> = vec![cells[i][j]];
+                        for (di, dj) in table.offsets() {
+                            let state = boundary.resolve(row + di, col + dj, rows, cols).map_or(0, |(r, c)| cells[r][c]);
+                            states.push(state);
+                        }
+                        table.next_state(&states)
+                    },
+                    (None, None) => {
+                        let mut neighbours: u8 = 0;
+                        for (bit, (di, dj)) in NEIGHBOURS.iter().enumerate() {
+                            neighbours |= (is_alive(row + di, col + dj) as u8) << bit;
+                        }
+                        rule.next_state(cells[i][j], neighbours)
+                    },
+                };
+            }
+        }
+        next
+    }
+
+    /// Make a rule where each configuration of neighbours causes birth or survival at random,
+    /// sometimes with decaying states
+    fn random_rule(rng: &mut impl Rng) -> Rule {
+        let states: u8 = if rng.gen_bool(0.5) { 2 } else { rng.gen_range(3..=6) };
+        let mut rule = Rule {
+            birth: [false; 256],
+            survival: [false; 256],
+            states,
+            neighbourhood: Neighbourhood::Moore,
+            ltl: None,
+            table: None,
+            wolfram: None,
+            turmite: None,
+        };
+        for config in 0..256 {
+            rule.birth[config] = rng.gen_bool(0.3);
+            rule.survival[config] = rng.gen_bool(0.4);
+        }
+        rule
+    }
+
+    /// Make a Larger than Life rule with a small radius and random ranges
+    fn random_ltl_rule(rng: &mut impl Rng) -> Rule {
+        let radius: usize = rng.gen_range(1..=3);
+        let most: usize = (2 * radius + 1) * (2 * radius + 1);
+        let mut span = || {
+            let min: usize = rng.gen_range(0..most / 2);
+            (min, rng.gen_range(min..=most))
+        };
+        let (birth, survival) = (span(), span());
+        let ltl = LargerThanLife {
+            radius,
+            neighbourhood: [Neighbourhood::Moore, Neighbourhood::VonNeumann, Neighbourhood::Hexagonal][rng.gen_range(0..3)],
+            is_center_counted: rng.gen_bool(0.5),
+            birth,
+            survival,
+        };
+        let states: u8 = if rng.gen_bool(0.5) { 2 } else { rng.gen_range(3..=6) };
+        Rule { birth: [false; 256], survival: [false; 256], states, neighbourhood: ltl.neighbourhood, ltl: Some(ltl), table: None, wolfram: None, turmite: None }
+    }
+
+    #[test]
+    fn simulate_matches_reference() {
+        for seed in 0..CASES {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let rows: usize = rng.gen_range(1..=MAX_SIZE);
+            let cols: usize = rng.gen_range(1..=MAX_SIZE);
+            let boundary: Boundary = BOUNDARIES[rng.gen_range(0..BOUNDARIES.len())];
+
+            // half of the cases use well-known rules, and the rest random life-like or Larger than
+            // Life rules; one-dimensional rules and turmites are not simulated here
+            let presets: Vec<Rule> = crate::rule::PRESETS.iter()
+                .map(|p| Rule::parse(p).unwrap())
+                .filter(|rule| rule.wolfram.is_none() && rule.turmite.is_none())
+                .collect();
+            let rule: Rule = if rng.gen_bool(0.25) {
+                random_rule(&mut rng)
+            } else if rng.gen_bool(0.33) {
+                random_ltl_rule(&mut rng)
+            } else {
+                presets[rng.gen_range(0..presets.len())].clone()
+            };
+
+            let mut cells: Grid = vec![vec![0; cols]; rows];
+            let density: f64 = rng.gen_range(0.0..=1.0);
+            random_fill(&mut cells, Region::whole(rows, cols), density, &mut rng);
+
+            for generation in 0..GENERATIONS {
+                let expected: Grid = reference(&cells, &rule, boundary);
+                cells = simulate(cells, &rule, boundary);
+                assert_eq!(cells, expected,
+                    "seed {} ({}x{}, {}, {:?}) differs at generation {}",
+                    seed, rows, cols, rule, boundary, generation + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn resize_keeps_anchored_cells() {
+        let mut cells: Grid = vec![vec![0; 4]; 4];
+        cells[0][0] = 1;
+        cells[2][1] = 1;
+
+        let centered = resize(&cells, 8, 6, Anchor::Center);
+        assert!(centered[2][1] == 1 && centered[4][2] == 1);
+        assert_eq!(centered.iter().flatten().filter(|&&c| c == 1).count(), 2);
+
+        let top_left = resize(&cells, 2, 2, Anchor::TopLeft);
+        assert_eq!(top_left, vec![vec![1, 0], vec![0, 0]]);
+    }
+
+    #[test]
+    fn regions_are_clipped_to_the_grid() {
+        let region = Region { row: 2, col: 3, rows: 4, cols: 4 };
+        assert_eq!(region.clip(8, 8), Some(region));
+        assert_eq!(region.clip(4, 5), Some(Region { row: 2, col: 3, rows: 2, cols: 2 }));
+        assert_eq!(region.clip(2, 8), None);
+
+        // a selection left over from a larger world fills only the part still inside it
+        let mut cells: Grid = vec![vec![0; 4]; 4];
+        random_fill(&mut cells, region, 1.0, &mut StdRng::seed_from_u64(1));
+        assert_eq!(cells.iter().flatten().filter(|&&c| c == 1).count(), 2);
+        random_fill(&mut cells, Region { row: 6, col: 0, rows: 2, cols: 2 }, 1.0, &mut StdRng::seed_from_u64(1));
+        assert_eq!(cells.iter().flatten().filter(|&&c| c == 1).count(), 2);
+    }
+
+    #[test]
+    fn void_clears_right_edge_of_wide_world() {
+        // a block touching the right edge of a world wider than it is tall
+        let mut cells: Grid = vec![vec![0; 9]; 4];
+        for (row, col) in [(1, 7), (1, 8), (2, 7), (2, 8)] {
+            cells[row][col] = 1;
+        }
+        let next = simulate(cells, &Rule::default(), Boundary::Void);
+        assert!(next.iter().all(|row| row[8] == 0));
+    }
+
+    #[test]
+    fn void_handles_tall_world() {
+        // a world taller than it is wide, which used to index past the last column
+        let mut cells: Grid = vec![vec![0; 4]; 9];
+        for row in &mut cells[3..6] {
+            row[2] = 1;
+        }
+        let next = simulate(cells.clone(), &Rule::default(), Boundary::Void);
+        assert_eq!(next, reference(&cells, &Rule::default(), Boundary::Void));
+    }
+
+    #[test]
+    fn wrap_reaches_first_row_from_last_row() {
+        // a blinker lying along the last row turns into one crossing into the first row
+        let mut cells: Grid = vec![vec![0; 6]; 6];
+        cells[5][2..5].fill(1);
+        let next = simulate(cells, &Rule::default(), Boundary::Wrap);
+        assert!(next[4][3] == 1 && next[5][3] == 1 && next[0][3] == 1);
+        assert_eq!(next.iter().flatten().filter(|&&c| c == 1).count(), 3);
+    }
+
+    #[test]
+    fn generations_cells_decay_before_dying() {
+        // in Brian's Brain a lone live cell cannot survive, so it decays and then dies
+        let rule: Rule = Rule::parse("B2/S/C3").unwrap();
+        let mut cells: Grid = vec![vec![0; 5]; 5];
+        cells[2][2] = 1;
+        let next = simulate(cells, &rule, Boundary::Wrap);
+        assert_eq!(next[2][2], 2);
+        let next = simulate(next, &rule, Boundary::Wrap);
+        assert_eq!(next[2][2], 0);
+    }
+
+    #[test]
+    fn ltl_parses_and_prints() {
+        let text = "R5,C0,M1,S34..58,B34..45,NM";
+        let rule: Rule = Rule::parse(text).unwrap();
+        let ltl: LargerThanLife = rule.ltl.unwrap();
+        assert_eq!((ltl.radius, ltl.birth, ltl.survival), (5, (34, 45), (34, 58)));
+        assert!(ltl.is_center_counted);
+        assert_eq!(rule.to_string(), text);
+        assert!(Rule::parse("R2,C0,M0,S3..1,B2..4,NM").is_err());
+    }
+
+    #[test]
+    fn neighbourhoods_parse_and_print() {
+        for text in ["B2/S34H", "B1/S012V", "B2/S/C3H", "B3/S23@111101111"] {
+            assert_eq!(Rule::parse(text).unwrap().to_string(), text);
+        }
+
+        // a custom mask of the von Neumann neighbours behaves the same as the V suffix
+        let custom: Rule = Rule::parse("B2/S13@010101010").unwrap();
+        let von_neumann: Rule = Rule::parse("B2/S13V").unwrap();
+        assert_eq!((custom.birth, custom.survival), (von_neumann.birth, von_neumann.survival));
+
+        assert!(Rule::parse("B5/S2V").is_err());
+        assert!(Rule::parse("B2a/S3H").is_err());
+        assert!(Rule::parse("B3/S23@111111111").is_err());
+    }
+
+    #[test]
+    fn hexagonal_ignores_two_corners() {
+        let rule: Rule = Rule::parse("B1/SH").unwrap();
+        let mut cells: Grid = vec![vec![0; 5]; 5];
+        cells[1][3] = 1;
+        let next = simulate(cells.clone(), &rule, Boundary::Void);
+        assert_eq!(next[2][2], 0, "the top right corner is not a neighbour");
+
+        cells[1][3] = 0;
+        cells[1][1] = 1;
+        let next = simulate(cells, &rule, Boundary::Void);
+        assert_eq!(next[2][2], 1, "the top left corner is a neighbour");
+    }
+
+    #[test]
+    fn wireworld_electron_follows_wire() {
+        let rule: Rule = Rule::parse("WireWorld").unwrap();
+        assert_eq!((rule.states, rule.to_string().as_str()), (4, "WireWorld"));
+        let mut cells: Grid = vec![vec![0; 8]; 3];
+        cells[1] = vec![0, 3, 3, 2, 1, 3, 3, 0];
+        let next = simulate(cells, &rule, Boundary::Void);
+        assert_eq!(next[1], vec![0, 3, 3, 3, 2, 1, 3, 0]);
+    }
+
+    #[test]
+    fn one_dimensional_rules_fill_rows_then_scroll() {
+        let rule: Rule = Rule::parse("W30").unwrap();
+        let wolfram: Wolfram = rule.wolfram.unwrap();
+        let mut cells: Grid = vec![vec![0; 7]; 3];
+        cells[0][3] = 1;
+        let row = simulate_line(&mut cells, 0, &wolfram, Boundary::Void);
+        let row = simulate_line(&mut cells, row, &wolfram, Boundary::Void);
+        assert_eq!(row, 2);
+        assert_eq!(cells, vec![
+            vec![0, 0, 0, 1, 0, 0, 0],
+            vec![0, 0, 1, 1, 1, 0, 0],
+            vec![0, 1, 1, 0, 0, 1, 0],
+        ]);
+
+        // once the last row is reached, the diagram scrolls up
+        let row = simulate_line(&mut cells, row, &wolfram, Boundary::Void);
+        assert_eq!(row, 2);
+        assert_eq!(cells[2], vec![1, 1, 0, 1, 1, 1, 1]);
+
+        // a totalistic rule counts the cell itself, and wraps around the ends of the row
+        let rule: Rule = Rule::parse("T10").unwrap();
+        let mut cells: Grid = vec![vec![1, 0, 0, 0, 0], vec![0; 5]];
+        simulate_line(&mut cells, 0, &rule.wolfram.unwrap(), Boundary::Wrap);
+        assert_eq!(cells[1], vec![1, 1, 0, 0, 1]);
+
+        for text in ["W30", "T10", "T52,R2"] {
+            assert_eq!(Rule::parse(text).unwrap().to_string(), text);
+        }
+        assert!(Rule::parse("W256").is_err());
+        assert!(Rule::parse("W30,R2").is_err());
+    }
+
+    #[test]
+    fn random_updates_repeat_with_the_same_seed() {
+        let rule = Rule::default();
+        let mut cells: Grid = vec![vec![0; 10]; 10];
+        random_fill(&mut cells, Region::whole(10, 10), 0.4, &mut StdRng::seed_from_u64(1));
+        let run = |mode: UpdateMode, probability: f64, noise: f64, seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            simulate_randomly(cells.clone(), &rule, Boundary::Wrap, mode, probability, noise, &mut rng)
+        };
+
+        for mode in [UpdateMode::Stochastic, UpdateMode::Asynchronous] {
+            assert_eq!(run(mode, 0.5, 0.1, 7), run(mode, 0.5, 0.1, 7), "{:?}", mode);
+        }
+
+        // updating every cell, or none, leaves nothing to chance
+        let next: Grid = simulate(cells.clone(), &rule, Boundary::Wrap);
+        assert_eq!(run(UpdateMode::Synchronous, 0.5, 0.0, 7), next);
+        assert_eq!(run(UpdateMode::Stochastic, 1.0, 0.0, 7), next);
+        assert_eq!(run(UpdateMode::Stochastic, 0.0, 0.0, 7), cells);
+
+        // full noise flips every cell after the update
+        let flipped: Grid = next.iter().map(|row| row.iter().map(|&c| (c == 0) as u8).collect()).collect();
+        assert_eq!(run(UpdateMode::Synchronous, 0.5, 1.0, 7), flipped);
+    }
+
+    #[test]
+    fn asynchronous_updates_change_one_cell_at_a_time() {
+        // every grid a blinker can reach in a 5x5 void world when single cells follow B3/S23 one
+        // at a time, counting neighbours by hand; the edges stay dead, leaving the middle 3x3
+        let follow_rule = |cells: &Grid, i: usize, j: usize| -> u8 {
+            let count: usize = cells[i - 1..=i + 1].iter().enumerate()
+                .flat_map(|(di, row)| row[j - 1..=j + 1].iter().enumerate().map(move |(dj, &c)| (di, dj, c)))
+                .filter(|&(di, dj, c)| (di, dj) != (1, 1) && c == 1)
+                .count();
+            (count == 3 || (count == 2 && cells[i][j] == 1)) as u8
+        };
+        let mut blinker: Grid = vec![vec![0; 5]; 5];
+        blinker[2][1..4].fill(1);
+        let mut reachable: Vec<Grid> = vec![blinker.clone()];
+        let mut index = 0;
+        while index < reachable.len() {
+            let cells: Grid = reachable[index].clone();
+            for i in 1..4 {
+                for j in 1..4 {
+                    let mut next: Grid = cells.clone();
+                    next[i][j] = follow_rule(&cells, i, j);
+                    if !reachable.contains(&next) {
+                        reachable.push(next);
+                    }
+                }
+            }
+            index += 1;
+        }
+
+        // each generation of an asynchronous run is one of those grids, and the order the cells
+        // are updated in makes a difference
+        let rule = Rule::default();
+        let mut firsts: Vec<Grid> = Vec::new();
+        for seed in 0..50 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut cells: Grid = blinker.clone();
+            for generation in 0..10 {
+                cells = simulate_randomly(cells, &rule, Boundary::Void, UpdateMode::Asynchronous, 0.5, 0.0, &mut rng);
+                assert!(reachable.contains(&cells), "seed {} generation {}: {:?}", seed, generation, cells);
+                if generation == 0 && !firsts.contains(&cells) {
+                    firsts.push(cells.clone());
+                }
+            }
+        }
+        assert!(firsts.len() > 1);
+
+        // a synchronous blinker turns upright, which no order of single updates can reach
+        let upright: Grid = simulate(blinker, &rule, Boundary::Void);
+        assert!(!reachable.contains(&upright));
+    }
+}
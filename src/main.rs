@@ -1,33 +1,73 @@
+pub mod args;
+pub mod boundary;
+pub mod config;
 pub mod controls;
+pub mod detect;
 pub mod draw;
 pub mod file;
+pub mod hensel;
+pub mod keys;
 pub mod life;
+pub mod rule;
+pub mod search;
+pub mod table;
 pub mod text;
+pub mod theme;
+pub mod turmite;
 pub mod ui;
+pub mod undo;
+pub mod widget;
 
 use std::cmp::max;
+use std::process;
+use std::thread;
 use std::time::{Duration, Instant};
 
-use controls::{calc_slider, in_pause, in_play, in_slider, in_upload, in_wrap, render_pause, render_play, render_slider, render_upload, render_wrap};
-use file::upload;
-use sdl2::image::LoadTexture;
+use args::{parse_args, parse_size, usage, Args};
+use boundary::Boundary;
+use config::{Config, FillConfig, UpdateConfig};
+use controls::{load_icon, render_overlay, Controls};
+use detect::{Detector, Periodicity};
+use file::{load, save, upload, Upload};
+use keys::{Action, Keymap};
+use rule::Rule;
+use search::{default_region, place_parent, Predecessor, PredecessorSearch};
 use text::TextCache;
-use ui::{Cell, render_cell, render_grid, Vector2};
-use life::simulate;
+use theme::{Theme, LIGHT};
+use turmite::Ant;
+use ui::{cell_color, grid_alpha, grid_dimensions, hex_shift, state_colors, BUFFER_SIZE, CellField, GridLines, render_grid, render_ants, render_major_lines, render_selection, Vector2, ViewMode};
+use life::{clear_extra_states, random_fill, resize, simulate, simulate_line, simulate_randomly, Anchor, FillRegion, Grid, History, Region, UpdateMode};
+use undo::UndoStack;
+use widget::{Icon, Widget};
 
-use sdl2::event::Event;
-use sdl2::mouse::MouseState;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::{Cursor, MouseState, SystemCursor};
 use sdl2::pixels::Color;
+use sdl2::render::BlendMode;
 use sdl2::render::{Canvas, Texture};
 use sdl2::rwops;
 use sdl2::ttf::{Font, Sdl2TtfContext, self};
 use sdl2::video::Window;
 use sdl2::{EventPump, Sdl, VideoSubsystem};
 
-// speed for the simulation
-const MIN_SPEED: u64 = 300;
-const MAX_SPEED: u64 = 1;
-const DEFAULT_SPEED: u64 = 1;
+// speed for the simulation, in generations per second
+const MIN_SPEED: u32 = 1;
+const MAX_SPEED: u32 = 10_000;
+const DEFAULT_SPEED: u32 = 60;
+
+// most generations to compute in one frame, so that slow machines are not overwhelmed
+const MAX_GENERATIONS_PER_FRAME: u32 = 1_000;
+
+// default cap on frames per second, and how long to block for input when idle
+const DEFAULT_FRAME_CAP: u32 = 60;
+const IDLE_TIMEOUT_MS: u32 = 500;
+
+// time given to a predecessor search in each frame
+const SEARCH_SLICE: Duration = Duration::from_millis(20);
 
 // font byte array
 const FONT_BYTES: &[u8] = include_bytes!("../assets/fonts/FiraSans-Regular.ttf");
@@ -35,27 +75,220 @@ const FONT_BYTES: &[u8] = include_bytes!("../assets/fonts/FiraSans-Regular.ttf")
 // icon byte arrays
 const UPLOAD_BYTES: &[u8] = include_bytes!("../assets/icons/upload.png");
 
-// size of the simulation
-const SIMULATED_ROWS: usize = 120;
-const SIMULATED_COLS: usize = 120;
+// default size of the simulation
+const DEFAULT_ROWS: usize = 120;
+const DEFAULT_COLS: usize = 120;
 const MIN_CELL_SIZE: i32 = 5;
+const DEFAULT_CELL_SIZE: i32 = 30;
+
+// number of cells to move the view by when panning
+const PAN_STEP: i32 = 5;
+
+// probability of each cell being alive in a random soup, and the size of a centred soup box
+const DEFAULT_FILL_DENSITY: f64 = 0.5;
+const DEFAULT_FILL_BOX_SIZE: usize = 32;
+
+// probability of each cell changing in a generation under stochastic updates
+const DEFAULT_UPDATE_PROBABILITY: f64 = 0.5;
+
+// most generations to run without a window before giving up on the pattern repeating
+const DEFAULT_HEADLESS_GENERATIONS: u64 = 1_000;
+
+// grid lines are hidden when cells are this size or smaller
+const DEFAULT_GRID_FADE_SIZE: i32 = 8;
+
+// default size of the window
+const DEFAULT_WINDOW_WIDTH: u32 = 1280;
+const DEFAULT_WINDOW_HEIGHT: u32 = 720;
+
+/// Convert a speed into the length of the speed slider, which is on a logarithmic scale
+fn slider_from_speed(speed: u32) -> f32 {
+    ((speed as f32).ln() - (MIN_SPEED as f32).ln())
+        / ((MAX_SPEED as f32).ln() - (MIN_SPEED as f32).ln())
+}
+
+/// Convert the length of the speed slider into a speed, which is on a logarithmic scale
+fn speed_from_slider(slider_length: f32) -> u32 {
+    let ratio: f32 = MAX_SPEED as f32 / MIN_SPEED as f32;
+    (MIN_SPEED as f32 * ratio.powf(slider_length)).round() as u32
+}
+
+/// Get the backend (row, col) cell under the given screen coordinates, if there is one
+/// The grid on screen has the given dimensions and starts at the given (x, y) backend cell
+fn cell_at(
+    x: i32,
+    y: i32,
+    cell_size: i32,
+    grid_dim: (i32, i32),
+    cells_start: (usize, usize),
+    is_hex: bool,
+) -> Option<(usize, usize)> {
+    // on a hexagonal grid each row is shifted sideways by a different amount
+    let x: i32 = if is_hex {
+        let row: i32 = (y - BUFFER_SIZE).div_euclid(cell_size);
+        x - hex_shift(row, grid_dim.0, cell_size)
+    } else {
+        x
+    };
+    let grid_vec = Vector2::new(x, y).to_grid(grid_dim.0, grid_dim.1, cell_size);
+    if grid_vec.x >= 0 && grid_vec.y >= 0 {
+        Some((cells_start.1 + grid_vec.y as usize, cells_start.0 + grid_vec.x as usize))
+    } else {
+        None
+    }
+}
+
+/// Simulate one generation, where one-dimensional rules draw it on the row after the current
+/// one and move the current row along, and turmites move each of the ants one step
+/// Other rules update their cells as the update settings say, using the given generator
+fn step(
+    cells: Grid,
+    rule: &Rule,
+    boundary: Boundary,
+    line: &mut usize,
+    ants: &mut Vec<Ant>,
+    update: &UpdateConfig,
+    rng: &mut StdRng,
+) -> Grid {
+    let mut cells = cells;
+    if let Some(wolfram) = &rule.wolfram {
+        let row: usize = (*line).min(cells.len() - 1);
+        *line = simulate_line(&mut cells, row, wolfram, boundary);
+    } else if let Some(turmite) = &rule.turmite {
+        turmite.step(&mut cells, ants, boundary);
+    } else {
+        cells = simulate_randomly(cells, rule, boundary, update.mode, update.probability, update.noise, rng);
+    }
+    cells
+}
+
+/// Check whether a pattern can be watched for repeating under the given rule and updates,
+/// which it cannot when updates are random or the rule moves ants or draws a diagram
+fn can_detect(rule: &Rule, update: &UpdateConfig) -> bool {
+    rule.wolfram.is_none() && rule.turmite.is_none()
+        && update.mode == UpdateMode::Synchronous && update.noise == 0.0
+}
+
+/// Run the pattern given on the command line without a window until it repeats or runs out of
+/// generations, printing how it repeats
+fn run_headless(args: Args) -> Result<(), String> {
+    let (mut cells, rule): (Grid, Rule) = match &args.pattern {
+        Some(path) => {
+            let pattern = load(path, args.rows, args.cols)?;
+            (pattern.cells, pattern.rule.unwrap_or(args.rule))
+        },
+        None => (vec![vec![0; args.cols]; args.rows], args.rule),
+    };
+    if !can_detect(&rule, &args.update) {
+        return Err(format!("Cannot tell whether a pattern repeats under {} or with random updates", rule));
+    }
+    clear_extra_states(&mut cells, rule.states);
+    if args.is_soup {
+        let seed: u64 = fill_soup(&mut cells, &args.fill, None, None);
+        println!("Soup seed: {}", seed);
+    }
+
+    let mut detector = Detector::default();
+    detector.sync(&cells);
+    for generation in 0..=args.generations {
+        if let Some(periodicity) = detector.found() {
+            println!("Generation {}: {}", generation, periodicity);
+            return Ok(());
+        }
+        cells = simulate(cells, &rule, args.boundary);
+        detector.observe(&cells);
+    }
+    println!("No repetition within {} generations", args.generations);
+    Ok(())
+}
+
+/// Place an ant in the middle of the world when switching to a turmite rule with no ants, and
+/// take the ants away under any other rule
+fn place_ants(ants: &mut Vec<Ant>, rule: &Rule, cells: &[Vec<u8>]) {
+    if rule.turmite.is_none() {
+        ants.clear();
+    } else if ants.is_empty() {
+        ants.push(Ant::new(cells.len() / 2, cells[0].len() / 2));
+    }
+}
+
+/// Bring a grid brought back by undo or redo in line with the current rule and size, since it may
+/// have been remembered under a rule with more states or before the world was resized, cutting
+/// the ants and the selection down to the grid
+fn fit_to_rule(cells: &mut Grid, rule: &Rule, ants: &mut Vec<Ant>, selection: &mut Option<Region>) {
+    clear_extra_states(cells, rule.states);
+    let (rows, cols) = (cells.len(), cells[0].len());
+    ants.retain(|ant| ant.row < rows && ant.col < cols);
+    *selection = selection.and_then(|region| region.clip(rows, cols));
+    place_ants(ants, rule, cells);
+}
+
+/// Fill part of the grid with a random soup as set up in the fill settings, returning the seed
+/// used so that the soup can be reproduced
+/// One-dimensional rules fill just the given row, starting their space-time diagram from it
+fn fill_soup(cells: &mut [Vec<u8>], fill: &FillConfig, selection: Option<Region>, line: Option<usize>) -> u64 {
+    let rows = cells.len();
+    let cols = cells[0].len();
+    let region: Region = match (line, fill.region, selection) {
+        (Some(row), _, _) => Region { row, col: 0, rows: 1, cols },
+        (None, FillRegion::Selection, Some(selected)) => selected,
+        (None, FillRegion::Box, _) => Region::centred(fill.box_size, rows, cols),
+        _ => Region::whole(rows, cols),
+    };
+
+    let seed: u64 = fill.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    random_fill(cells, region, fill.density, &mut StdRng::seed_from_u64(seed));
+    seed
+}
 
 fn main() {
+    // read user preferences, then parse command-line arguments on top of them
+    let mut config: Config = Config::load();
+    let args: Args = match parse_args(std::env::args().skip(1), &config) {
+        Ok(Some(a)) => a,
+        Ok(None) => {
+            println!("{}", usage());
+            return;
+        },
+        Err(e) => {
+            eprintln!("ERROR: {}\n\n{}", e, usage());
+            process::exit(1);
+        },
+    };
+    if args.is_headless {
+        if let Err(e) = run_headless(args) {
+            eprintln!("ERROR: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+    let mut rule = args.rule;
+
     // initialize SDL contexts and windows
     let sdl_context: Sdl = sdl2::init().unwrap();
     let video_subsystem: VideoSubsystem = sdl_context.video().unwrap();
-    let window: Window = video_subsystem.window("Test", 1280, 720)
-        .position_centered()
+    let mut window_builder = video_subsystem.window("Test", config.window.width, config.window.height);
+    match (config.window.x, config.window.y) {
+        (Some(x), Some(y)) => window_builder.position(x, y),
+        _ => window_builder.position_centered(),
+    };
+    let window: Window = window_builder
         .resizable()
         .build()
         .unwrap();
-    let mut canvas: Canvas<Window> = window.into_canvas().build().unwrap();
+    let mut canvas_builder = window.into_canvas();
+    if config.vsync {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+    let mut canvas: Canvas<Window> = canvas_builder.build().unwrap();
+    canvas.set_blend_mode(BlendMode::Blend);
     let mut event_pump: EventPump = sdl_context.event_pump().unwrap();
     let texture_creator = canvas.texture_creator();
     let _image_context = sdl2::image::init(sdl2::image::InitFlag::PNG).unwrap();
 
-    // initialize icon textures
-    let upload_texture: Texture = texture_creator.load_texture_bytes(UPLOAD_BYTES).unwrap();
+    // initialize icon textures and the controls that use them
+    let upload_texture: Texture = load_icon(&texture_creator, UPLOAD_BYTES).unwrap();
+    let mut controls: Controls = Controls::new(upload_texture);
     
     // initialize font and text cache
     let ttf_context: Sdl2TtfContext = ttf::init().expect("Failed to init TTF context");
@@ -66,155 +299,630 @@ fn main() {
         ).unwrap();
     let mut text_cache = TextCache::new(&texture_creator, &font);
 
-    // initialize backend simulation grid
-    let mut cells: Vec<Vec<bool>> = vec![vec![false; SIMULATED_COLS]; SIMULATED_ROWS];
+    // initialize the texture that the cells are rendered into, which is recreated whenever the
+    // size of the world changes
+    let mut cell_field: CellField = CellField::new(&texture_creator, args.rows, args.cols).unwrap();
+    let mut cell_field_size: (usize, usize) = (args.rows, args.cols);
+
+    // initialize backend simulation grid, loading the pattern given on the command line along
+    // with the rule it was made for
+    let mut cells: Grid = match &args.pattern {
+        Some(path) => match load(path, args.rows, args.cols) {
+            Ok(pattern) => {
+                rule = pattern.rule.unwrap_or(rule);
+                pattern.cells
+            },
+            Err(e) => {
+                eprintln!("ERROR: {}", e);
+                process::exit(1);
+            },
+        },
+        None => vec![vec![0; args.cols]; args.rows],
+    };
+    clear_extra_states(&mut cells, rule.states);
+
+    // start from a random soup if asked to, remembering its seed to show on screen
+    let mut fill: FillConfig = args.fill;
+    let mut selection: Option<Region> = None;
+    let mut selection_anchor: Option<(usize, usize)> = None;
+    let mut last_seed: Option<u64> = None;
+    // row of the current generation of a one-dimensional rule, which moves down the space-time
+    // diagram as it runs
+    let mut line: usize = 0;
+    // ants walking over the world under a turmite rule
+    let mut ants: Vec<Ant> = Vec::new();
+    place_ants(&mut ants, &rule, &cells);
+    if args.is_soup {
+        last_seed = Some(fill_soup(&mut cells, &fill, selection, rule.wolfram.map(|_| line)));
+    }
+    let mut history: History = History::new(&cells);
+
+    // random updates and noise come from their own generator, so that a run can be repeated by
+    // giving its seed
+    let mut update: UpdateConfig = args.update;
+    let mut update_rng = StdRng::seed_from_u64(update.seed.unwrap_or_else(|| rand::thread_rng().gen()));
 
     // state variables
-    let mut is_simulating = false;
+    let mut is_simulating = args.is_running;
     let mut is_slider_moving = false;
-    let mut slider_length: f32 = 1.0;
-    let mut is_wrap = false;
-    let mut cell_size = 30;
+    let mut boundary: Boundary = args.boundary;
+    let mut cell_size = args.cell_size;
+    let mut theme: Theme = Theme::from_name(&config.theme).unwrap_or(LIGHT);
+    let mut grid_lines: GridLines = config.grid_lines;
+    let mut view: ViewMode = config.view;
+    let mut pan: (i32, i32) = (0, 0);
+    let mut is_help_shown = false;
+    let mut undo_stack = UndoStack::default();
+    let mut is_tracking_window = args.is_tracking_window;
+    let mut resize_anchor: Anchor = config.resize_anchor;
+    let mut size_input: Option<String> = None;
+    let mut size_error: Option<String> = None;
+    let mut pending_size: Option<(usize, usize)> = None;
+    // watches the pattern as it runs, to tell whether and how it repeats
+    let mut detector = Detector::default();
+    // predecessor search under way, which runs a little each frame
+    let mut predecessor_search: Option<PredecessorSearch> = None;
+    // result of the last predecessor search, shown until the next action
+    let mut notice: Option<String> = None;
+    let keymap = Keymap::new(&config.keys);
 
-    // keep track of time between loops to update simulation
+    // keep track of time between loops to update simulation, along with the fraction of a
+    // generation that is due but has not yet been computed
     let mut last_updated = Instant::now();
-    let mut interval = Duration::from_millis(DEFAULT_SPEED);
+    let mut speed: u32 = args.speed;
+    let mut pending_generations: f64 = 0.0;
+
+    // settings as they were at startup, which may have come from the command line for this run
+    // only, so they are written back on exit only if they were changed while running
+    let startup_rule: String = rule.to_string();
+    let startup_boundary: Boundary = boundary;
+    let startup_speed: u32 = speed;
+    let startup_tracking: bool = is_tracking_window;
+    let startup_fill: FillRegion = fill.region;
+    let startup_update: UpdateMode = update.mode;
+
+    // keep track of whether the screen needs to be redrawn
+    let mut is_dirty = true;
+
+    // modifier keys are checked when clicking to tell selecting apart from editing
+    let keyboard = sdl_context.keyboard();
+
+    // show a hand cursor over the controls, and keep track of the mouse for hover effects
+    let arrow_cursor: Cursor = Cursor::from_system(SystemCursor::Arrow).unwrap();
+    let hand_cursor: Cursor = Cursor::from_system(SystemCursor::Hand).unwrap();
+    let mut is_hand_cursor = false;
+    let mut is_mouse_in_window = true;
+    let mut was_tooltip_pending = false;
+
+    // typing into the resize dialog is done through text input events
+    let text_input = video_subsystem.text_input();
+    text_input.stop();
 
     // render loop
     'running: loop {
-        // determine if a grid can be rendered
+        // cells on the edges of a void world are always dead, so they are kept off screen
+        let margin: i32 = if boundary == Boundary::Void { 1 } else { 0 };
+
+        // when tracking the window, resize the world to the number of cells that fit on screen
+        if is_tracking_window {
+            let visible: Result<(i32, i32), String> =
+                grid_dimensions(&canvas, cell_size, i32::MAX, i32::MAX);
+            if let Ok((visible_rows, visible_cols)) = visible {
+                let world_rows = max(3, visible_rows + 2 * margin) as usize;
+                let world_cols = max(3, visible_cols + 2 * margin) as usize;
+                if (world_rows, world_cols) != (cells.len(), cells[0].len()) {
+                    cells = resize(&cells, world_rows, world_cols, resize_anchor);
+                    ants.retain(|ant| ant.row < world_rows && ant.col < world_cols);
+                    history = History::new(&cells);
+                    selection = None;
+                    is_dirty = true;
+                }
+            }
+        }
+        let rows: usize = cells.len();
+        let cols: usize = cells[0].len();
+
+        // start watching for repeats over if the grid has been edited since the last generation
+        if can_detect(&rule, &update) {
+            detector.sync(&cells);
+        }
+
+        // simulate Life, computing as many generations as are due since the last frame
+        let curr_time = Instant::now();
+        if is_simulating {
+            pending_generations +=
+                curr_time.duration_since(last_updated).as_secs_f64() * speed as f64;
+            let generations: u32 = (pending_generations as u32).min(MAX_GENERATIONS_PER_FRAME);
+            pending_generations = pending_generations.fract();
+            for _ in 0..generations {
+                cells = step(cells, &rule, boundary, &mut line, &mut ants, &update, &mut update_rng);
+                history.record(&cells);
+                if can_detect(&rule, &update) {
+                    detector.observe(&cells);
+                }
+            }
+            if generations > 0 {
+                is_dirty = true;
+            }
+        } else {
+            pending_generations = 0.0;
+        }
+        last_updated = curr_time;
+
+        // determine if a grid can be rendered, and how many cells fit on screen
         let mut is_rendered = true;
-        
-        // render grid
-        canvas.set_draw_color(Color::WHITE);
-        canvas.clear();
         let mut grid_dim: (i32, i32) = (0, 0);  // (rows, cols)
-        match render_grid(&mut canvas,
-                          cell_size,
-                          SIMULATED_ROWS as i32 - 2,
-                          SIMULATED_COLS as i32 - 2) {
+        match grid_dimensions(&canvas, cell_size, rows as i32 - 2 * margin, cols as i32 - 2 * margin) {
             Ok(res) => grid_dim = res,
             Err(_) => is_rendered = false,
         }
-        
-        // simulate Life
-        if is_simulating {
-            let curr_time = Instant::now();
-            if curr_time.duration_since(last_updated) > interval {
-                cells = simulate(cells, is_wrap);
-                last_updated = curr_time;
-            }
-        }
 
-        // render cells
+        // figure out where the grid on the screen maps to the grid on the backend, keeping the
+        // view panned within the bounds of the backend grid
         let mut cells_start: (usize, usize) = (0, 0);
-        if is_rendered == true {
-            // figure out where the grid on the screen maps to the grid on the backend
-            let cells_start_x = (SIMULATED_COLS / 2) as usize - (grid_dim.1 / 2) as usize;
-            let cells_start_y = (SIMULATED_ROWS / 2) as usize - (grid_dim.0 / 2) as usize;
-            cells_start = (cells_start_x, cells_start_y);
-
-            // render all cells in the backend that correspond to the screen
-            for i in 0..grid_dim.0 {
-                for j in 0..grid_dim.1 {
-                    if cells[cells_start_y + i as usize][cells_start_x + j as usize] {
-                        let grid_vec = Vector2::new(j, i);
-                        let new_cell = Cell::from_grid(grid_vec, cell_size);
-                        render_cell(&mut canvas, new_cell, cell_size);
-                    }
-                }
+        if is_rendered {
+            let centered_x = (cols / 2) as i32 - grid_dim.1 / 2;
+            let centered_y = (rows / 2) as i32 - grid_dim.0 / 2;
+            let cells_start_x = (centered_x + pan.0).clamp(margin, cols as i32 - margin - grid_dim.1);
+            let cells_start_y = (centered_y + pan.1).clamp(margin, rows as i32 - margin - grid_dim.0);
+            pan = (cells_start_x - centered_x, cells_start_y - centered_y);
+            cells_start = (cells_start_x as usize, cells_start_y as usize);
+        }
+
+        // if slider is in moving state, update slider length and set speed
+        if is_slider_moving {
+            let mouse_state: MouseState = MouseState::new(&event_pump);
+            speed = speed_from_slider(controls.speed.value_at(mouse_state.x()));
+            is_dirty = true;
+        }
+
+        // bring the controls up to date with the simulation state, then lay them out
+        controls.play.icon = if is_simulating { Icon::Pause } else { Icon::Play };
+        controls.speed.value = slider_from_speed(speed);
+        controls.speed.text = format!("{}/s", speed);
+        controls.boundary.text = boundary.label().to_string();
+        controls.grid_lines.text = grid_lines.label().to_string();
+        controls.view.text = view.label().to_string();
+        controls.theme.text = theme.name.to_uppercase();
+        controls.status.text = format!("{}    {}x{}{}    FILL {} {}%", rule, rows, cols,
+            if is_tracking_window { " FIT" } else { "" }, fill.region.label(),
+            (fill.density * 100.0).round());
+        if let Some(seed) = last_seed {
+            controls.status.text.push_str(&format!("    SEED {}", seed));
+        }
+        if update.mode != UpdateMode::Synchronous {
+            controls.status.text.push_str(&format!("    UPDATE {}", update.mode.label()));
+            if update.mode == UpdateMode::Stochastic {
+                controls.status.text.push_str(&format!(" {}%", (update.probability * 100.0).round()));
             }
         }
+        if update.noise > 0.0 {
+            controls.status.text.push_str(&format!("    NOISE {}%", (update.noise * 100.0).round()));
+        }
+        // an empty grid is not worth pointing out
+        let periodicity: Option<Periodicity> = detector.found().filter(|&p| p != Periodicity::Empty);
+        if let Some(periodicity) = periodicity.filter(|_| can_detect(&rule, &update)) {
+            controls.status.text.push_str(&format!("    {}", periodicity.label()));
+        }
+        if predecessor_search.is_some() {
+            controls.status.text.push_str("    SEARCHING FOR PREDECESSOR");
+        }
+        if let Some(text) = &notice {
+            controls.status.text.push_str(&format!("    {}", text));
+        }
+        controls.layout(canvas.output_size().unwrap(), &text_cache);
 
-        // render pause and play buttons according to simulation state
-        if is_simulating {
-            render_pause(&mut canvas);
+        // highlight the control under the mouse, and redraw once its tooltip is due
+        let mouse_state: MouseState = MouseState::new(&event_pump);
+        let (mouse_x, mouse_y) = if is_mouse_in_window {
+            (mouse_state.x(), mouse_state.y())
         } else {
-            render_play(&mut canvas);
+            (-1, -1)
+        };
+        if controls.update_mouse(mouse_x, mouse_y, mouse_state.left()) {
+            is_dirty = true;
+        }
+        if controls.is_hovered() != is_hand_cursor {
+            is_hand_cursor = controls.is_hovered();
+            if is_hand_cursor { hand_cursor.set() } else { arrow_cursor.set() }
+        }
+        if was_tooltip_pending && !controls.is_tooltip_pending() {
+            is_dirty = true;
         }
+        was_tooltip_pending = controls.is_tooltip_pending();
 
-        // render slider controls for simulation speed
-        let speed_text: &str = &interval.as_millis().to_string();
-        render_slider(&mut canvas, &mut text_cache, speed_text, slider_length);
+        // only redraw the screen if something has changed since the last frame
+        if is_dirty {
+            canvas.set_draw_color(theme.background);
+            canvas.clear();
 
-        // render upload icon
-        render_upload(&mut canvas, &upload_texture);
+            // render cells, with grid lines on top
+            if is_rendered {
+                if cell_field_size != (rows, cols) {
+                    cell_field = CellField::new(&texture_creator, rows, cols).unwrap();
+                    cell_field_size = (rows, cols);
+                }
+                let is_hex: bool = rule.is_hexagonal();
+                let palette: Vec<Color> = state_colors(&theme, &rule);
+                cell_field.update(rows, cols, |i, j| {
+                    cell_color(view, &theme, &history, &palette, cells[i][j], i, j)
+                });
+                cell_field.render(&mut canvas, cell_size, grid_dim, cells_start, is_hex);
+                render_ants(&mut canvas, theme.cell_new, cell_size, grid_dim, cells_start, &ants);
 
-        // render wrap button
-        render_wrap(&mut canvas, &mut text_cache, is_wrap);
+                if let Some(region) = selection {
+                    render_selection(&mut canvas, theme.selection, cell_size, grid_dim, cells_start, region, is_hex);
+                }
 
-        // if slider is in moving state, update slider length and set speed
-        if is_slider_moving {
-            let mouse_state: MouseState = MouseState::new(&event_pump);
-            slider_length = calc_slider(mouse_state.x());
-            let new_interval =
-                ((MIN_SPEED - MAX_SPEED) as f32 * slider_length) as u64;
-            interval = Duration::from_millis(MIN_SPEED - new_interval);
+                let mut grid_color = theme.grid;
+                grid_color.a = match grid_lines {
+                    GridLines::Hidden => 0,
+                    _ => grid_alpha(cell_size, config.grid_fade_size),
+                };
+                render_grid(&mut canvas, grid_color, cell_size, grid_dim, is_hex);
+                if grid_lines == GridLines::Major {
+                    render_major_lines(&mut canvas, theme.grid, cell_size, grid_dim, cells_start, is_hex);
+                }
+            }
+
+            // render the controls around the grid
+            controls.render(&mut canvas, &theme, &mut text_cache);
+
+            // render the tooltip of the control under the mouse
+            controls.render_tooltip(&mut canvas, &theme, &mut text_cache, &keymap);
+
+            // render the help overlay on top of everything else
+            if is_help_shown {
+                render_overlay(&mut canvas, &theme, &mut text_cache, &keymap.describe());
+            }
+
+            // render the resize dialog, showing what has been typed so far
+            if let Some(input) = &size_input {
+                let mut lines: Vec<(&str, String)> = vec![
+                    ("World size (rows x columns)", format!("{}_", input)),
+                    ("Keep in place (Tab)", resize_anchor.label().to_string()),
+                    ("", String::new()),
+                    ("Enter to resize, Esc to cancel", String::new()),
+                ];
+                if let Some(e) = &size_error {
+                    lines.push((e.as_str(), String::new()));
+                }
+                render_overlay(&mut canvas, &theme, &mut text_cache, &lines);
+            }
+
+            canvas.present();
+            text_cache.evict_unused();
+            is_dirty = false;
+        }
+
+        // when there is nothing to animate, block until there is input instead of polling
+        let mut events: Vec<Event> = Vec::new();
+        let is_idle: bool =
+            !is_simulating && !is_slider_moving && !is_dirty && !controls.is_tooltip_pending();
+        if is_idle {
+            if let Some(event) = event_pump.wait_event_timeout(IDLE_TIMEOUT_MS) {
+                events.push(event);
+            }
         }
+        events.extend(event_pump.poll_iter());
 
-        // handle events
-        for event in event_pump.poll_iter() {
+        // handle events, redrawing after any of them, and collect the actions they trigger
+        let mut actions: Vec<Action> = Vec::new();
+        for event in events {
+            is_dirty = true;
             match event {
                 Event::Quit { .. } => {
                     break 'running
                 },
+                Event::KeyDown { keycode: Some(keycode), keymod, .. } => {
+                    // while the resize dialog is open, keys edit the size it shows
+                    if let Some(input) = &mut size_input {
+                        match keycode {
+                            Keycode::RETURN | Keycode::KP_ENTER => match parse_size(input) {
+                                Ok(size) => {
+                                    pending_size = Some(size);
+                                    size_input = None;
+                                    text_input.stop();
+                                },
+                                Err(e) => size_error = Some(e),
+                            },
+                            Keycode::ESCAPE => {
+                                size_input = None;
+                                text_input.stop();
+                            },
+                            Keycode::BACKSPACE => {
+                                input.pop();
+                            },
+                            Keycode::TAB => resize_anchor = resize_anchor.next(),
+                            _ => {},
+                        }
+                    } else if is_help_shown && keycode == Keycode::ESCAPE {
+                        is_help_shown = false;
+                    } else if let Some(action) = keymap.action(keycode, keymod) {
+                        actions.push(action);
+                    }
+                },
+                Event::TextInput { text, .. } => {
+                    if let Some(input) = &mut size_input {
+                        let is_size_char = |c: &char| c.is_ascii_digit() || c.eq_ignore_ascii_case(&'x');
+                        input.extend(text.chars().filter(is_size_char));
+                    }
+                },
+                Event::Window { win_event: WindowEvent::Enter, .. } => is_mouse_in_window = true,
+                Event::Window { win_event: WindowEvent::Leave, .. } => is_mouse_in_window = false,
                 Event::MouseWheel { y, .. } => {
                     cell_size = max(MIN_CELL_SIZE, cell_size + y);
                 },
                 Event::MouseButtonDown { x, y, .. } => {
                     // if initial click is in slider, set slider moving variable to true
-                    if is_rendered && in_slider(&canvas, x, y) {
+                    if is_rendered && controls.speed.contains(x, y) {
                         is_slider_moving = true;
-                        slider_length = calc_slider(x as i32);
-                        let new_interval =
-                            ((MIN_SPEED - MAX_SPEED) as f32 * slider_length) as u64;
-                        interval = Duration::from_millis(MIN_SPEED - new_interval);
+                        speed = speed_from_slider(controls.speed.value_at(x));
+                    }
+
+                    // shift-dragging on the grid selects a region instead of toggling a cell
+                    else if is_rendered && keyboard.mod_state().intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        selection_anchor = cell_at(x, y, cell_size, grid_dim, cells_start, rule.is_hexagonal());
+                        selection = selection_anchor.map(|cell| Region::between(cell, cell));
+                    }
+                },
+                Event::MouseMotion { x, y, .. } => {
+                    if let (Some(anchor), Some(cell)) =
+                        (selection_anchor, cell_at(x, y, cell_size, grid_dim, cells_start, rule.is_hexagonal())) {
+                        selection = Some(Region::between(anchor, cell));
                     }
                 },
                 Event::MouseButtonUp { x, y, .. } => {
-                    if is_rendered {
-                        // get click and convert to grid coordinates
-                        let click_vec = Vector2::new(x, y);
-                        let grid_vec = click_vec.to_grid(grid_dim.0, grid_dim.1, cell_size);
-
+                    if selection_anchor.is_some() {
+                        selection_anchor = None;
+                    } else if is_rendered {
                         // ensure click is within grid and update backend grid
-                        if grid_vec.x >= 0 && grid_vec.y >= 0 {
-                            let grid_y = cells_start.1 + grid_vec.y as usize;
-                            let grid_x = cells_start.0 + grid_vec.x as usize;
-                            cells[grid_y][grid_x] = !cells[grid_y][grid_x];
+                        if let Some((grid_y, grid_x)) = cell_at(x, y, cell_size, grid_dim, cells_start, rule.is_hexagonal()) {
+                            undo_stack.push(&cells);
+                            cells[grid_y][grid_x] = rule.drawn_state(cells[grid_y][grid_x]);
+                            history.set(grid_y, grid_x, cells[grid_y][grid_x] != 0);
+                            // one-dimensional rules carry on from the row drawn on
+                            line = grid_y;
                         }
 
-                        // check upload icon clicks
-                        else if in_upload(x, y) {
-                            cells = match upload() {
-                                Ok(c) => c,
+                        // check control clicks
+                        else if let Some(action) = controls.action_at(x, y) {
+                            actions.push(action);
+                        }
+
+                        // if slider was in moving state, get it out of moving state
+                        is_slider_moving = false;
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        // perform the actions triggered by keys and controls
+        for action in actions {
+            notice = None;
+            match action {
+                Action::PlayPause => {
+                    // remember the grid before a run so that the run can be undone
+                    if !is_simulating {
+                        undo_stack.push(&cells);
+                    }
+                    is_simulating = !is_simulating;
+                },
+                Action::Step => {
+                    undo_stack.push(&cells);
+                    if can_detect(&rule, &update) {
+                        detector.sync(&cells);
+                    }
+                    cells = step(cells, &rule, boundary, &mut line, &mut ants, &update, &mut update_rng);
+                    history.record(&cells);
+                    if can_detect(&rule, &update) {
+                        detector.observe(&cells);
+                    }
+                },
+                Action::Clear => {
+                    undo_stack.push(&cells);
+                    for row in &mut cells {
+                        row.fill(0);
+                    }
+                    line = 0;
+                    ants.clear();
+                    place_ants(&mut ants, &rule, &cells);
+                    history = History::new(&cells);
+                },
+                Action::Randomize => {
+                    undo_stack.push(&cells);
+                    // one-dimensional rules start a new diagram from a random first row
+                    if rule.wolfram.is_some() {
+                        cells.iter_mut().for_each(|row| row.fill(0));
+                        line = 0;
+                    }
+                    last_seed = Some(fill_soup(&mut cells, &fill, selection, rule.wolfram.map(|_| line)));
+                    history = History::new(&cells);
+                },
+                Action::NextFillRegion => fill.region = fill.region.next(),
+                Action::NextUpdateMode => {
+                    update.mode = update.mode.next();
+                    detector = Detector::default();
+                },
+                Action::ClearSelection => selection = None,
+                Action::ZoomIn => cell_size += 1,
+                Action::ZoomOut => cell_size = max(MIN_CELL_SIZE, cell_size - 1),
+                Action::PanUp => pan.1 -= PAN_STEP,
+                Action::PanDown => pan.1 += PAN_STEP,
+                Action::PanLeft => pan.0 -= PAN_STEP,
+                Action::PanRight => pan.0 += PAN_STEP,
+                Action::Undo => {
+                    if undo_stack.undo(&mut cells) {
+                        fit_to_rule(&mut cells, &rule, &mut ants, &mut selection);
+                        history = History::new(&cells);
+                    }
+                },
+                Action::Redo => {
+                    if undo_stack.redo(&mut cells) {
+                        fit_to_rule(&mut cells, &rule, &mut ants, &mut selection);
+                        history = History::new(&cells);
+                    }
+                },
+                Action::Save => {
+                    match save(&cells, &rule, config.last_directory.as_deref()) {
+                        Ok(path) => {
+                            config.last_directory = path.parent().map(|p| p.to_path_buf());
+                        },
+                        Err(e) => eprintln!("ERROR: {}", e),
+                    }
+                },
+                Action::Load => {
+                    match upload(cells.len(), cells[0].len(), config.last_directory.as_deref()) {
+                        Ok((Upload::Pattern(pattern), path)) => {
+                            config.last_directory = path.parent().map(|p| p.to_path_buf());
+                            undo_stack.push(&cells);
+                            cells = pattern.cells;
+                            rule = pattern.rule.unwrap_or(rule);
+                            detector = Detector::default();
+                            clear_extra_states(&mut cells, rule.states);
+                            line = 0;
+                            place_ants(&mut ants, &rule, &cells);
+                            history = History::new(&cells);
+                        },
+                        // a rule table replaces the rule, keeping the cells that fit within it
+                        Ok((Upload::Rule(table_rule), path)) => {
+                            config.last_directory = path.parent().map(|p| p.to_path_buf());
+                            rule = table_rule;
+                            detector = Detector::default();
+                            clear_extra_states(&mut cells, rule.states);
+                            place_ants(&mut ants, &rule, &cells);
+                        },
+                        Err(e) => eprintln!("ERROR: {}", e),
+                    }
+                },
+                Action::NextBoundary => {
+                    boundary = boundary.next();
+                    detector = Detector::default();
+                },
+                Action::NextRule => {
+                    rule = rule.next_preset();
+                    detector = Detector::default();
+                    clear_extra_states(&mut cells, rule.states);
+                    place_ants(&mut ants, &rule, &cells);
+                },
+                Action::NextTheme => theme = theme.next(),
+                Action::NextGridLines => grid_lines = grid_lines.next(),
+                Action::NextView => view = view.next(),
+                Action::ResizeWorld => {
+                    size_input = Some(format!("{}x{}", rows, cols));
+                    size_error = None;
+                    text_input.start();
+                },
+                Action::TrackWindow => is_tracking_window = !is_tracking_window,
+                Action::AddAnt => {
+                    if rule.turmite.is_some() {
+                        if let Some((row, col)) = cell_at(mouse_x, mouse_y, cell_size, grid_dim, cells_start, false) {
+                            ants.push(Ant::new(row, col));
+                        }
+                    }
+                },
+                Action::FindPredecessor => {
+                    // pressing it again while searching stops the search
+                    if predecessor_search.take().is_some() {
+                        notice = Some("PREDECESSOR SEARCH STOPPED".to_string());
+                    } else {
+                        // search the selection, or else the live cells and the dead cells around them
+                        match selection.or_else(|| default_region(&cells)) {
+                            Some(region) => match PredecessorSearch::new(&cells, region, &rule, boundary) {
+                                Ok(search) => predecessor_search = Some(search),
                                 Err(e) => {
                                     eprintln!("ERROR: {}", e);
-                                    cells
+                                    notice = Some("NO PREDECESSOR SEARCH".to_string());
                                 },
-                            };
+                            },
+                            None => notice = Some("NOTHING TO SEARCH".to_string()),
                         }
+                    }
+                },
+                Action::Help => is_help_shown = !is_help_shown,
+            }
+        }
 
-                        // check wrap button clicks
-                        else if in_wrap(&canvas, &text_cache, x, y, is_wrap) {
-                            is_wrap = !is_wrap;
-                        }
+        // resize the world to the size entered in the dialog, which stops tracking the window
+        if let Some((new_rows, new_cols)) = pending_size.take() {
+            undo_stack.push(&cells);
+            cells = resize(&cells, new_rows, new_cols, resize_anchor);
+            ants.retain(|ant| ant.row < new_rows && ant.col < new_cols);
+            history = History::new(&cells);
+            selection = None;
+            is_tracking_window = false;
+        }
 
-                        // check play button clicks
-                        else if is_simulating {
-                            if in_pause(&canvas, x, y) { is_simulating = false; }
-                        } else if !is_simulating {
-                            if in_play(&canvas, x, y) { is_simulating = true; }
-                        }
+        // carry on with the predecessor search, placing the parent once found unless the pattern
+        // has changed in the meantime
+        if let Some(search) = &mut predecessor_search {
+            if let Some(outcome) = search.run(Instant::now() + SEARCH_SLICE) {
+                let region: Region = search.region();
+                let is_current: bool = search.is_target(&cells);
+                predecessor_search = None;
+                notice = Some(match outcome {
+                    Ok(_) if !is_current => "PATTERN CHANGED DURING SEARCH".to_string(),
+                    Ok(Predecessor::Found(parent)) => {
+                        undo_stack.push(&cells);
+                        place_parent(&mut cells, region, &parent);
+                        history = History::new(&cells);
+                        "PREDECESSOR FOUND".to_string()
+                    },
+                    Ok(Predecessor::None) => "NO PREDECESSOR (GARDEN OF EDEN)".to_string(),
+                    Err(e) => {
+                        eprintln!("ERROR: {}", e);
+                        "NO PREDECESSOR SEARCH".to_string()
+                    },
+                });
+            }
+            is_dirty = true;
+        }
 
-                        // if slider was in moving state, get it out of moving state
-                        if is_slider_moving { is_slider_moving = false; }
-                    }
-                },
-                _ => {}
+        // wait out the rest of the frame if a frame cap is set
+        if config.frame_cap > 0 {
+            let frame_time: Duration = Duration::from_secs(1) / config.frame_cap;
+            let elapsed: Duration = curr_time.elapsed();
+            if elapsed < frame_time {
+                thread::sleep(frame_time - elapsed);
             }
         }
-        
-        canvas.present();
+    }
+
+    // remember preferences for the next run
+    let window_size: (u32, u32) = canvas.window().size();
+    let window_position: (i32, i32) = canvas.window().position();
+    config.window.width = window_size.0;
+    config.window.height = window_size.1;
+    config.window.x = Some(window_position.0);
+    config.window.y = Some(window_position.1);
+    // a rule table loaded from elsewhere than the rules directory cannot be found again by name
+    if rule.to_string() != startup_rule {
+        match Rule::parse(&rule.to_string()) {
+            Ok(_) => config.rule = rule.to_string(),
+            Err(_) => eprintln!("WARNING: Not saving rule {}, which can only be loaded from its file", rule),
+        }
+    }
+    if boundary != startup_boundary {
+        config.boundary = boundary.name().to_string();
+    }
+    if speed != startup_speed {
+        config.speed = speed;
+    }
+    if fill.region != startup_fill {
+        config.fill.region = fill.region;
+    }
+    if update.mode != startup_update {
+        config.update.mode = update.mode;
+    }
+    if is_tracking_window != startup_tracking {
+        config.track_window = is_tracking_window;
+    }
+    config.theme = theme.name.to_string();
+    config.grid_lines = grid_lines;
+    config.view = view;
+    config.resize_anchor = resize_anchor;
+    if let Err(e) = config.save() {
+        eprintln!("WARNING: Could not save config: {}", e);
     }
 }
@@ -0,0 +1,518 @@
+use crate::hensel::{self, NEIGHBOURS};
+use crate::table::{self, RuleTable};
+use crate::turmite::Turmite;
+
+use std::path::Path;
+use std::rc::Rc;
+
+/// Well-known rules, in the order they are cycled through
+pub const PRESETS: [&str; 17] = [
+    "B3/S23",
+    "B36/S23",
+    "B3678/S34678",
+    "B2/S",
+    "B3/S012345678",
+    "B36/S125",
+    "B3/S12345",
+    "B2/S/C3",
+    "B2/S345/C4",
+    "B3/S2-i34q",
+    "B2/S34H",
+    "R5,C0,M1,S34..58,B34..45,NM",
+    "WireWorld",
+    "W30",
+    "W110",
+    "RL",
+    "RLLR",
+];
+
+// most states a rule can have, as each cell is stored in a byte
+pub const MAX_STATES: u32 = 255;
+
+// largest radius of a Larger than Life neighbourhood
+const MAX_RADIUS: usize = 500;
+
+// largest radius of a one-dimensional totalistic rule, so that every count has a bit of the code
+const MAX_LINE_RADIUS: usize = 10;
+
+/// Shape of the neighbourhood of cells counted around each cell
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Neighbourhood {
+    /// Every cell within the radius in both directions, making a square
+    Moore,
+    /// Every cell within the radius in steps along rows and columns, making a diamond
+    VonNeumann,
+    /// Every cell within the radius on a hexagonal grid, drawn with each row shifted half a cell
+    /// to the left of the row above, so that the corners to the top right and bottom left are
+    /// left out
+    Hexagonal,
+    /// Any of the eight neighbours, one bit each in the order of hensel::NEIGHBOURS
+    Custom(u8),
+}
+
+impl Neighbourhood {
+    /// Get which of the eight neighbours are in the neighbourhood, one bit each
+    pub fn mask(&self) -> u8 {
+        match self {
+            Neighbourhood::Moore => 0xff,
+            Neighbourhood::VonNeumann => 0x55,
+            Neighbourhood::Hexagonal => 0xdd,
+            Neighbourhood::Custom(mask) => *mask,
+        }
+    }
+
+    /// Get the range of columns of the neighbourhood in the given row, both relative to the
+    /// center cell, for the neighbourhoods that grow with the radius rather than custom ones
+    pub fn row_span(&self, radius: usize, row: isize) -> (isize, isize) {
+        let radius = radius as isize;
+        match self {
+            Neighbourhood::VonNeumann => {
+                let width: isize = radius - row.abs();
+                (-width, width)
+            },
+            Neighbourhood::Hexagonal => ((row - radius).max(-radius), (row + radius).min(radius)),
+            _ => (-radius, radius),
+        }
+    }
+}
+
+/// A Larger than Life rule, counting live cells within a radius and giving birth or survival when
+/// the count falls within an inclusive range
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct LargerThanLife {
+    pub radius: usize,
+    pub neighbourhood: Neighbourhood,
+    pub is_center_counted: bool,
+    pub birth: (usize, usize),
+    pub survival: (usize, usize),
+}
+
+impl LargerThanLife {
+    /// Parse the fields of a rule in Golly's notation, such as R5,C0,M1,S34..58,B34..45,NM,
+    /// returning the rule and its number of states
+    fn parse(text: &str) -> Result<(LargerThanLife, u8), String> {
+        let mut ltl = LargerThanLife {
+            radius: 1,
+            neighbourhood: Neighbourhood::Moore,
+            is_center_counted: false,
+            birth: (3, 3),
+            survival: (2, 3),
+        };
+        let mut states: u8 = 2;
+
+        for field in text.split(',') {
+            let field: &str = field.trim();
+            let (key, value) = field.split_at(field.chars().next().map_or(0, |c| c.len_utf8()));
+            match key {
+                "R" => {
+                    ltl.radius = match value.parse::<usize>() {
+                        Ok(r) if (1..=MAX_RADIUS).contains(&r) => r,
+                        _ => return Err(format!("Invalid radius {} in rule, expected 1 to {}", value, MAX_RADIUS)),
+                    };
+                },
+                // both 0 and 2 mean the usual two states
+                "C" => {
+                    states = match value.parse::<u32>() {
+                        Ok(0) => 2,
+                        Ok(n) if (2..=MAX_STATES).contains(&n) => n as u8,
+                        _ => return Err(format!("Invalid number of states {} in rule", value)),
+                    };
+                },
+                "M" => {
+                    ltl.is_center_counted = match value {
+                        "0" => false,
+                        "1" => true,
+                        _ => return Err(format!("Invalid middle cell setting {} in rule, expected 0 or 1", value)),
+                    };
+                },
+                "S" => ltl.survival = parse_span(value)?,
+                "B" => ltl.birth = parse_span(value)?,
+                "N" => {
+                    ltl.neighbourhood = match value {
+                        "M" => Neighbourhood::Moore,
+                        "N" => Neighbourhood::VonNeumann,
+                        "H" => Neighbourhood::Hexagonal,
+                        _ => return Err(format!("Invalid neighbourhood {} in rule, expected M, N or H", value)),
+                    };
+                },
+                _ => return Err(format!("Invalid field {} in rule", field)),
+            }
+        }
+        Ok((ltl, states))
+    }
+}
+
+/// A one-dimensional rule on a single row of two-state cells, given by Wolfram's numbering
+/// Elementary rules read the cell and its two neighbours as a binary number, from left to right,
+/// while totalistic rules count the live cells within the radius, including the cell itself; in
+/// both, that bit of the code is the next state
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Wolfram {
+    pub code: u64,
+    pub radius: usize,
+    pub is_totalistic: bool,
+}
+
+impl Wolfram {
+    /// Parse an elementary rule such as W30, or a totalistic rule such as T10, with a radius
+    /// after a comma for wider neighbourhoods as in T52,R2
+    /// Returns None if the text is not written as either
+    fn parse(text: &str) -> Option<Result<Wolfram, String>> {
+        let (code, radius) = match text.split_once(',') {
+            Some((code, radius)) => (code, radius.trim().strip_prefix(['R', 'r'])?),
+            None => (text, "1"),
+        };
+        let is_totalistic: bool = code.starts_with(['T', 't']);
+        let code: &str = code.trim().strip_prefix(['W', 'w', 'T', 't'])?;
+        if code.is_empty() || !code.chars().chain(radius.chars()).all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let radius: usize = match radius.parse() {
+            Ok(r) if (1..=MAX_LINE_RADIUS).contains(&r) && (is_totalistic || r == 1) => r,
+            _ => return Some(Err(format!("Invalid radius {} in rule, elementary rules have radius 1 and totalistic ones up to {}", radius, MAX_LINE_RADIUS))),
+        };
+        // each arrangement or count of live cells has a bit of the code
+        let bits: u32 = if is_totalistic { 2 * radius as u32 + 2 } else { 8 };
+        Some(match code.parse::<u64>() {
+            Ok(n) if n < 1 << bits => Ok(Wolfram { code: n, radius, is_totalistic }),
+            _ => Err(format!("Invalid rule number {}, expected 0 to {}", code, (1_u64 << bits) - 1)),
+        })
+    }
+
+    /// Given the states of the cells within the radius from left to right, including the cell
+    /// itself in the middle, determine its next state
+    pub fn next_state(&self, cells: &[u8]) -> u8 {
+        let index: usize = if self.is_totalistic {
+            cells.iter().filter(|&&c| c == 1).count()
+        } else {
+            cells.iter().fold(0, |index, &c| index << 1 | (c == 1) as usize)
+        };
+        (self.code >> index & 1) as u8
+    }
+}
+
+/// A life-like rule, storing which configurations of live neighbours cause birth and survival,
+/// indexed by a bit per neighbour in the order of hensel::NEIGHBOURS
+/// Rules with more than two states are Generations rules, where live cells that do not survive
+/// pass through decaying states before they die; only live cells count as neighbours
+/// Rules on other neighbourhoods within the eight neighbours ignore the neighbours left out
+/// Larger than Life rules count a wider neighbourhood instead, rule tables give the next state
+/// from the states of every neighbour, one-dimensional rules evolve a single row, and turmites
+/// move ants that paint the cells they walk over, all ignoring the birth and survival tables here
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Rule {
+    pub birth: [bool; 256],
+    pub survival: [bool; 256],
+    pub states: u8,
+    pub neighbourhood: Neighbourhood,
+    pub ltl: Option<LargerThanLife>,
+    pub table: Option<Rc<RuleTable>>,
+    pub wolfram: Option<Wolfram>,
+    pub turmite: Option<Turmite>,
+}
+
+impl Default for Rule {
+    /// Conway's Game of Life, B3/S23
+    fn default() -> Self {
+        Rule::parse("B3/S23").unwrap()
+    }
+}
+
+impl Rule {
+    /// Parse a rulestring in B/S notation (e.g. B3/S23), or the older S/B notation (e.g. 23/3)
+    /// Generations rules add the number of states, as in B2/S/C3 or the older /2/3
+    /// Larger than Life rules are given as comma separated fields, as in R5,C0,M1,S34..58,B34..45,NM
+    /// Counts may be followed by Hensel letters to choose which configurations of neighbours
+    /// they include, as in B2-a/S12
+    /// A V or H at the end counts only the von Neumann or hexagonal neighbours, and @ followed by
+    /// nine 0s and 1s, row by row around a 0 for the cell itself, counts any chosen neighbours
+    /// Rule tables are given by the path of a .rule file, or by name to find a built-in table or
+    /// one in the rules directory
+    /// One-dimensional rules are given by number, as in W30 or T10,R1
+    /// Turmites are given as a turn for each color, as in RL, or in Golly's turmite notation
+    pub fn parse(text: &str) -> Result<Rule, String> {
+        let text = text.trim();
+        if let Some(wolfram) = Wolfram::parse(text) {
+            let mut rule: Rule = Rule::parse("B/S")?;
+            rule.wolfram = Some(wolfram?);
+            return Ok(rule);
+        }
+        if let Some(turmite) = Turmite::parse(text) {
+            let turmite: Turmite = turmite?;
+            let mut rule: Rule = Rule::parse("B/S")?;
+            rule.states = turmite.colors;
+            rule.turmite = Some(turmite);
+            return Ok(rule);
+        }
+        if text.to_lowercase().ends_with(".rule") {
+            return Ok(Rule::from_table(table::load(Path::new(text))?));
+        }
+        if !text.contains(['/', ',', '@']) {
+            if let Some(table) = table::find(text)? {
+                return Ok(Rule::from_table(table));
+            }
+        }
+
+        if text.to_uppercase().starts_with('R') && text.contains(',') {
+            let (ltl, states) = LargerThanLife::parse(&text.to_uppercase())?;
+            return Ok(Rule {
+                birth: [false; 256],
+                survival: [false; 256],
+                states,
+                neighbourhood: ltl.neighbourhood,
+                ltl: Some(ltl),
+                table: None,
+                wolfram: None,
+                turmite: None,
+            });
+        }
+
+        // take the neighbourhood off the end of the rule
+        let (text, neighbourhood) = if let Some((text, mask)) = text.split_once('@') {
+            (text, Neighbourhood::Custom(parse_mask(mask)?))
+        } else if let Some(text) = text.strip_suffix(['V', 'v']) {
+            (text, Neighbourhood::VonNeumann)
+        } else if let Some(text) = text.strip_suffix(['H', 'h']) {
+            (text, Neighbourhood::Hexagonal)
+        } else {
+            (text, Neighbourhood::Moore)
+        };
+
+        let parts: Vec<&str> = text.split('/').collect();
+        if parts.len() != 2 && parts.len() != 3 {
+            return Err(format!("Invalid rule {}, expected the form B3/S23", text));
+        }
+
+        // the number of states may be written with a C or G in front
+        let states: u8 = match parts.get(2) {
+            Some(part) => {
+                let number: &str = part.trim_start_matches(['C', 'G', 'c', 'g']);
+                match number.parse::<u32>() {
+                    Ok(n) if (2..=MAX_STATES).contains(&n) => n as u8,
+                    _ => return Err(format!("Invalid number of states {} in rule", part)),
+                }
+            },
+            None => 2,
+        };
+
+        // figure out which part holds the birth counts and which holds the survival counts
+        let (birth_text, survival_text) =
+            if parts[0].starts_with(['B', 'b']) && parts[1].starts_with(['S', 's']) {
+                (&parts[0][1..], &parts[1][1..])
+            } else if parts[0].starts_with(['S', 's']) && parts[1].starts_with(['B', 'b']) {
+                (&parts[1][1..], &parts[0][1..])
+            } else {
+                (parts[1], parts[0])
+            };
+
+        Ok(Rule {
+            birth: parse_counts(birth_text, neighbourhood)?,
+            survival: parse_counts(survival_text, neighbourhood)?,
+            states,
+            neighbourhood,
+            ltl: None,
+            table: None,
+            wolfram: None,
+            turmite: None,
+        })
+    }
+
+    /// Make a rule from a rule table, taking its number of states and neighbourhood
+    pub fn from_table(table: RuleTable) -> Rule {
+        Rule {
+            birth: [false; 256],
+            survival: [false; 256],
+            states: table.states,
+            neighbourhood: table.neighbourhood,
+            ltl: None,
+            table: Some(Rc::new(table)),
+            wolfram: None,
+            turmite: None,
+        }
+    }
+
+    /// Whether the rule is on a hexagonal grid, so that its cells are drawn as one
+    pub fn is_hexagonal(&self) -> bool {
+        self.neighbourhood == Neighbourhood::Hexagonal
+    }
+
+    /// Get the preset after this rule, or the first preset if this rule is not one of them
+    pub fn next_preset(&self) -> Rule {
+        let presets: Vec<Rule> = PRESETS.iter().map(|p| Rule::parse(p).unwrap()).collect();
+        match presets.iter().position(|p| p == self) {
+            Some(index) => presets[(index + 1) % presets.len()].clone(),
+            None => presets[0].clone(),
+        }
+    }
+
+    /// Whether a dead cell with no live neighbours comes to life
+    pub fn is_born_alone(&self) -> bool {
+        match (&self.ltl, &self.table) {
+            (Some(ltl), _) => ltl.birth.0 == 0,
+            (None, Some(table)) => table.next_state(&[0; 9][..=table.offsets().len()]) != 0,
+            (None, None) => self.birth[0],
+        }
+    }
+
+    /// Get the state a cell is drawn as when clicked, which is the next state for rule tables
+    /// and turmites since any of their states may be drawn, and otherwise alive or dead in turn
+    pub fn drawn_state(&self, state: u8) -> u8 {
+        if self.table.is_some() || self.turmite.is_some() {
+            ((state as u32 + 1) % self.states as u32) as u8
+        } else {
+            (state == 0) as u8
+        }
+    }
+
+    /// Given the state of a cell and which of its neighbours are alive, one bit each, determine
+    /// its next state
+    pub fn next_state(&self, state: u8, neighbours: u8) -> u8 {
+        let index = neighbours as usize;
+        self.advance(state, self.birth[index], self.survival[index])
+    }
+
+    /// Given the state of a cell and its number of live neighbours under a Larger than Life rule,
+    /// determine its next state
+    pub fn next_state_counted(&self, ltl: &LargerThanLife, state: u8, count: usize) -> u8 {
+        let within = |span: (usize, usize)| (span.0..=span.1).contains(&count);
+        self.advance(state, within(ltl.birth), within(ltl.survival))
+    }
+
+    /// Determine the next state of a cell from whether it would be born or survive
+    /// State 0 is dead and state 1 is alive; any other state is decaying towards death
+    fn advance(&self, state: u8, is_born: bool, is_surviving: bool) -> u8 {
+        match state {
+            0 => is_born as u8,
+            1 if is_surviving => 1,
+            _ => ((state as u32 + 1) % self.states as u32) as u8,
+        }
+    }
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(table) = &self.table {
+            return write!(f, "{}", table.name);
+        }
+        if let Some(turmite) = &self.turmite {
+            return write!(f, "{}", turmite);
+        }
+        if let Some(wolfram) = &self.wolfram {
+            return match (wolfram.is_totalistic, wolfram.radius) {
+                (false, _) => write!(f, "W{}", wolfram.code),
+                (true, 1) => write!(f, "T{}", wolfram.code),
+                (true, radius) => write!(f, "T{},R{}", wolfram.code, radius),
+            };
+        }
+        if let Some(ltl) = &self.ltl {
+            let neighbourhood: &str = match ltl.neighbourhood {
+                Neighbourhood::VonNeumann => "N",
+                Neighbourhood::Hexagonal => "H",
+                _ => "M",
+            };
+            return write!(f, "R{},C{},M{},S{}..{},B{}..{},N{}",
+                ltl.radius,
+                if self.states > 2 { self.states } else { 0 },
+                ltl.is_center_counted as u8,
+                ltl.survival.0, ltl.survival.1,
+                ltl.birth.0, ltl.birth.1,
+                neighbourhood);
+        }
+
+        let (birth, survival) = match self.neighbourhood {
+            Neighbourhood::Moore => (hensel::format(&self.birth), hensel::format(&self.survival)),
+            _ => (format_counts(&self.birth, self.neighbourhood), format_counts(&self.survival, self.neighbourhood)),
+        };
+        write!(f, "B{}/S{}", birth, survival)?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        match self.neighbourhood {
+            Neighbourhood::Moore => Ok(()),
+            Neighbourhood::VonNeumann => write!(f, "V"),
+            Neighbourhood::Hexagonal => write!(f, "H"),
+            Neighbourhood::Custom(mask) => {
+                // lay the neighbours out row by row, around the cell itself
+                let mut grid: [char; 9] = ['0'; 9];
+                for (bit, (di, dj)) in NEIGHBOURS.iter().enumerate() {
+                    if mask & 1 << bit != 0 {
+                        grid[(3 * (di + 1) + dj + 1) as usize] = '1';
+                    }
+                }
+                write!(f, "@{}", grid.iter().collect::<String>())
+            },
+        }
+    }
+}
+
+/// Parse neighbour counts into a table of which configurations of live neighbours are included,
+/// where only the Moore neighbourhood may use Hensel letters and the other neighbourhoods only
+/// count the neighbours they include
+fn parse_counts(text: &str, neighbourhood: Neighbourhood) -> Result<[bool; 256], String> {
+    let table: [bool; 256] = hensel::parse(text)?;
+    if neighbourhood == Neighbourhood::Moore {
+        return Ok(table);
+    }
+
+    let mask: u8 = neighbourhood.mask();
+    if let Some(c) = text.chars().find(|c| !c.is_ascii_digit()) {
+        return Err(format!("Invalid {} in rule, letters can only be used with all eight neighbours", c));
+    }
+    if let Some(c) = text.chars().find(|c| c.to_digit(10).unwrap() > mask.count_ones()) {
+        return Err(format!("Invalid neighbour count {} in rule, there are only {} neighbours", c, mask.count_ones()));
+    }
+
+    // a configuration is included when its number of neighbours within the mask is one of the
+    // counts, which is found from the configuration with that many neighbours in a row
+    let mut masked = [false; 256];
+    for (config, is_included) in masked.iter_mut().enumerate() {
+        let count: u32 = (config as u8 & mask).count_ones();
+        *is_included = table[(1 << count) - 1];
+    }
+    Ok(masked)
+}
+
+/// Write the neighbour counts included in a table for a neighbourhood other than the Moore one
+fn format_counts(table: &[bool; 256], neighbourhood: Neighbourhood) -> String {
+    let mask: u8 = neighbourhood.mask();
+    (0..=mask.count_ones())
+        .filter(|&count| {
+            // find a configuration with this many neighbours inside the mask
+            let mut config: u8 = 0;
+            for bit in (0..8).filter(|&bit| mask & 1 << bit != 0).take(count as usize) {
+                config |= 1 << bit;
+            }
+            table[config as usize]
+        })
+        .map(|count| count.to_string())
+        .collect()
+}
+
+/// Parse a mask of neighbours written as nine 0s and 1s, row by row around a 0 for the cell
+/// itself, into a bit per neighbour
+fn parse_mask(text: &str) -> Result<u8, String> {
+    let cells: Vec<char> = text.chars().collect();
+    if cells.len() != 9 || cells[4] != '0' || cells.iter().any(|&c| c != '0' && c != '1') {
+        return Err(format!("Invalid neighbourhood @{}, expected nine 0s and 1s with 0 in the middle", text));
+    }
+    let mut mask: u8 = 0;
+    for (bit, (di, dj)) in NEIGHBOURS.iter().enumerate() {
+        if cells[(3 * (di + 1) + dj + 1) as usize] == '1' {
+            mask |= 1 << bit;
+        }
+    }
+    Ok(mask)
+}
+
+/// Parse an inclusive range of neighbour counts such as "34..58" into its ends
+fn parse_span(text: &str) -> Result<(usize, usize), String> {
+    let ends: Option<(usize, usize)> = match text.split_once("..") {
+        Some((min, max)) => min.parse().ok().zip(max.parse().ok()),
+        None => text.parse().ok().map(|n| (n, n)),
+    };
+    match ends {
+        Some((min, max)) if min <= max => Ok((min, max)),
+        _ => Err(format!("Invalid neighbour count range {} in rule", text)),
+    }
+}
@@ -0,0 +1,342 @@
+use crate::boundary::Boundary;
+use crate::file::bounds;
+use crate::hensel::NEIGHBOURS;
+use crate::life::{Grid, Region};
+use crate::rule::Rule;
+
+use std::collections::HashSet;
+use std::time::Instant;
+
+// most cells to try before giving up, so that a search that would take hours comes to an end
+const MAX_NODES: u64 = 100_000_000;
+
+// most dead ends remembered at once, which keeps the memory used in check
+const MAX_DEAD_ENDS: usize = 1_000_000;
+
+// cells tried between looks at the clock
+const NODES_PER_CHECK: u64 = 1_024;
+
+// parent cells not yet decided
+const UNKNOWN: u8 = 2;
+
+/// Outcome of a search for a predecessor
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Predecessor {
+    /// A parent for the region and the cells around it, whose top left cell is one up and one
+    /// left of the region's
+    Found(Grid),
+    /// No arrangement of cells within one cell of the region evolves into it, so the region is a
+    /// Garden of Eden as far as that bounded area goes
+    None,
+}
+
+/// Search for a grid of cells that evolves into a region of the grid in one generation, run a
+/// little at a time so that the window keeps responding
+/// The search backtracks cell by cell along the shorter side of the region, pruning whenever a
+/// cell of the region can no longer reach its target state
+pub struct PredecessorSearch {
+    region: Region,
+    target: Grid,
+    // states a target cell can reach, as bits, by the state of its center (dead, alive or
+    // undecided) and its live and undecided neighbours
+    reachable: Vec<u8>,
+    parent: Grid,
+    // cells to decide, one row or column of the parent after another
+    free: Vec<(usize, usize)>,
+    by_columns: bool,
+    // number of free cells decided, and the state to try next for each
+    depth: usize,
+    next: Vec<u8>,
+    // rows or columns that could not be continued, along with the two decided before them
+    dead_ends: HashSet<(usize, Vec<u8>)>,
+    nodes: u64,
+}
+
+impl PredecessorSearch {
+    /// Start a search of the given region and one cell around it, with everything beyond that and
+    /// beyond the edges of the world taken as dead, so only void worlds can be searched
+    /// Only two-state rules on the eight neighbours or fewer can be searched, such as B3/S23
+    pub fn new(cells: &[Vec<u8>], region: Region, rule: &Rule, boundary: Boundary) -> Result<PredecessorSearch, String> {
+        if rule.states != 2 || rule.ltl.is_some() || rule.table.is_some() || rule.wolfram.is_some() || rule.turmite.is_some() {
+            return Err(format!("Predecessors can only be searched for under two-state life-like rules, not {}", rule));
+        }
+        if boundary != Boundary::Void {
+            return Err("Predecessors can only be searched for in a void world".to_string());
+        }
+        if region.clip(cells.len(), cells[0].len()) != Some(region) {
+            return Err("The region to search for a predecessor lies outside the world".to_string());
+        }
+
+        let (rows, cols) = (region.rows + 2, region.cols + 2);
+        let target: Grid = cells[region.row..region.row + region.rows].iter()
+            .map(|row| row[region.col..region.col + region.cols].to_vec())
+            .collect();
+
+        // parent cells beyond the edges of the world are fixed as dead
+        let mut parent: Grid = vec![vec![UNKNOWN; cols]; rows];
+        let mut free: Vec<(usize, usize)> = Vec::new();
+        for (i, row) in parent.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                let is_inside: bool = (region.row + i).checked_sub(1).is_some_and(|r| r < cells.len())
+                    && (region.col + j).checked_sub(1).is_some_and(|c| c < cells[0].len());
+                if is_inside {
+                    free.push((i, j));
+                } else {
+                    *cell = 0;
+                }
+            }
+        }
+
+        // a wide region is searched a column at a time, so that fewer cells are undecided at once
+        let by_columns: bool = cols > rows;
+        if by_columns {
+            free.sort_by_key(|&(i, j)| (j, i));
+        }
+
+        let next: Vec<u8> = vec![0; free.len()];
+        Ok(PredecessorSearch {
+            region, target, reachable: reachable_states(rule), parent, free, by_columns,
+            depth: 0, next, dead_ends: HashSet::new(), nodes: 0,
+        })
+    }
+
+    /// Get the region searched
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Check whether the region of the grid still holds the pattern searched for
+    pub fn is_target(&self, cells: &[Vec<u8>]) -> bool {
+        let region: Region = self.region;
+        cells.get(region.row..region.row + region.rows).is_some_and(|rows| rows.iter().zip(&self.target)
+            .all(|(row, target)| row.get(region.col..region.col + region.cols) == Some(&target[..])))
+    }
+
+    /// Carry on searching until the given time, returning the outcome once the search is over,
+    /// which is an error if it took too long
+    pub fn run(&mut self, until: Instant) -> Option<Result<Predecessor, String>> {
+        loop {
+            if self.depth == self.free.len() {
+                return Some(Ok(Predecessor::Found(self.parent.clone())));
+            }
+
+            // once two rows or columns are decided, only they affect the target cells still to
+            // come, so one that could not follow them before cannot now either
+            let (i, j) = self.free[self.depth];
+            let key: Option<(usize, Vec<u8>)> = self.line_start();
+            if self.next[self.depth] == 0 && key.as_ref().is_some_and(|key| self.dead_ends.contains(key)) {
+                self.next[self.depth] = 2;
+            }
+
+            // go back to the cell before once both states have been tried
+            if self.next[self.depth] == 2 {
+                self.parent[i][j] = UNKNOWN;
+                if let Some(key) = key.filter(|_| self.dead_ends.len() < MAX_DEAD_ENDS) {
+                    self.dead_ends.insert(key);
+                }
+                if self.depth == 0 {
+                    return Some(Ok(Predecessor::None));
+                }
+                self.depth -= 1;
+                continue;
+            }
+
+            self.nodes += 1;
+            if self.nodes > MAX_NODES {
+                return Some(Err(format!("Gave up searching for a predecessor after {} cells", MAX_NODES)));
+            }
+            if self.nodes.is_multiple_of(NODES_PER_CHECK) && Instant::now() >= until {
+                return None;
+            }
+
+            // dead cells are tried first, so that sparser parents are found
+            self.parent[i][j] = self.next[self.depth];
+            self.next[self.depth] += 1;
+            if self.is_consistent(i, j) {
+                self.depth += 1;
+                if let Some(next) = self.next.get_mut(self.depth) {
+                    *next = 0;
+                }
+            }
+        }
+    }
+
+    /// Get the row or column the next free cell starts, if it starts one, along with the cells
+    /// of the two before it
+    fn line_start(&self) -> Option<(usize, Vec<u8>)> {
+        let line = |(i, j): (usize, usize)| if self.by_columns { j } else { i };
+        let current: usize = line(self.free[self.depth]);
+        let is_start: bool = self.depth == 0 || line(self.free[self.depth - 1]) != current;
+        if !is_start || current < 2 {
+            return None;
+        }
+        let cells: Vec<u8> = if self.by_columns {
+            self.parent.iter().flat_map(|row| row[current - 2..current].to_vec()).collect()
+        } else {
+            self.parent[current - 2..current].concat()
+        };
+        Some((current, cells))
+    }
+
+    /// Check that every target cell next to the given parent cell can still reach its state
+    fn is_consistent(&self, i: usize, j: usize) -> bool {
+        // the target cell at (ti, tj) sits at (ti + 1, tj + 1) in the parent
+        for ti in i.saturating_sub(2)..=i.min(self.target.len() - 1) {
+            for tj in j.saturating_sub(2)..=j.min(self.target[0].len() - 1) {
+                if !self.can_reach(ti, tj) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Check whether some way of filling in the undecided cells around a target cell gives it
+    /// its target state
+    fn can_reach(&self, ti: usize, tj: usize) -> bool {
+        let (ci, cj) = (ti + 1, tj + 1);
+        let (mut alive, mut unknown): (usize, usize) = (0, 0);
+        for (bit, (di, dj)) in NEIGHBOURS.iter().enumerate() {
+            match self.parent[ci.wrapping_add_signed(*di)][cj.wrapping_add_signed(*dj)] {
+                1 => alive |= 1 << bit,
+                UNKNOWN => unknown |= 1 << bit,
+                _ => {},
+            }
+        }
+        let index: usize = (self.parent[ci][cj] as usize) << 16 | alive << 8 | unknown;
+        self.reachable[index] & (1 << self.target[ti][tj]) != 0
+    }
+}
+
+/// Get the states a cell can reach under the rule, as bits, for each state of its own (dead,
+/// alive or undecided) and each set of live and undecided neighbours
+fn reachable_states(rule: &Rule) -> Vec<u8> {
+    let mut reachable: Vec<u8> = vec![0; 3 << 16];
+    for center in [0, 1, UNKNOWN] {
+        let centers: &[u8] = match center {
+            UNKNOWN => &[0, 1],
+            0 => &[0],
+            _ => &[1],
+        };
+        for alive in 0..=u8::MAX {
+            for unknown in (0..=u8::MAX).filter(|unknown| unknown & alive == 0) {
+                // try every subset of the undecided neighbours, from the empty set up to all of them
+                let mut states: u8 = 0;
+                let mut subset: u8 = 0;
+                loop {
+                    for &state in centers {
+                        states |= 1 << rule.next_state(state, alive | subset);
+                    }
+                    if subset == unknown {
+                        break;
+                    }
+                    subset = subset.wrapping_sub(unknown) & unknown;
+                }
+                reachable[(center as usize) << 16 | (alive as usize) << 8 | unknown as usize] = states;
+            }
+        }
+    }
+    reachable
+}
+
+/// Get the region a predecessor is searched for when nothing is selected, which is the live cells
+/// and the dead cells around them, so that the parent grows nothing next to the pattern
+pub fn default_region(cells: &[Vec<u8>]) -> Option<Region> {
+    let (live_rows, live_cols) = bounds(cells)?;
+    let top: usize = live_rows.start().saturating_sub(1);
+    let left: usize = live_cols.start().saturating_sub(1);
+    let bottom: usize = (live_rows.end() + 1).min(cells.len() - 1);
+    let right: usize = (live_cols.end() + 1).min(cells[0].len() - 1);
+    Some(Region::between((top, left), (bottom, right)))
+}
+
+/// Write a parent found for the given region into the grid, over the region and the cells around
+/// it that lie within the world
+pub fn place_parent(cells: &mut [Vec<u8>], region: Region, parent: &[Vec<u8>]) {
+    for (i, row) in parent.iter().enumerate() {
+        for (j, &state) in row.iter().enumerate() {
+            let cell = (region.row + i).checked_sub(1)
+                .zip((region.col + j).checked_sub(1))
+                .and_then(|(r, c)| cells.get_mut(r)?.get_mut(c));
+            if let Some(cell) = cell {
+                *cell = state;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::life::simulate;
+    use std::time::Duration;
+
+    /// Run a search to the end, however long it takes
+    fn find_predecessor(cells: &[Vec<u8>], region: Region, rule: &Rule, boundary: Boundary) -> Result<Predecessor, String> {
+        let mut search = PredecessorSearch::new(cells, region, rule, boundary)?;
+        loop {
+            if let Some(outcome) = search.run(Instant::now() + Duration::from_secs(1)) {
+                return outcome;
+            }
+        }
+    }
+
+    #[test]
+    fn found_predecessors_evolve_into_the_target() {
+        let rule = Rule::default();
+        let mut cells: Grid = vec![vec![0; 8]; 8];
+        for (i, j) in [(2, 3), (3, 4), (4, 2), (4, 3), (4, 4)] {
+            cells[i][j] = 1;
+        }
+        let region: Region = default_region(&cells).unwrap();
+        let parent: Grid = match find_predecessor(&cells, region, &rule, Boundary::Void).unwrap() {
+            Predecessor::Found(parent) => parent,
+            Predecessor::None => panic!("a glider has a predecessor"),
+        };
+
+        // place the parent in an empty world and check it reaches the glider
+        let mut world: Grid = vec![vec![0; 8]; 8];
+        place_parent(&mut world, region, &parent);
+        let next: Grid = simulate(world, &rule, Boundary::Void);
+        for i in region.row..region.row + region.rows {
+            assert_eq!(next[i][region.col..region.col + region.cols], cells[i][region.col..region.col + region.cols]);
+        }
+    }
+
+    #[test]
+    fn cells_that_cannot_arise_have_no_predecessor() {
+        // a lone cell is its own parent when it survives alone, but nothing can make it when no
+        // cell is ever born or survives
+        let mut cells: Grid = vec![vec![0; 5]; 5];
+        cells[2][2] = 1;
+        let region: Region = default_region(&cells).unwrap();
+        assert!(matches!(find_predecessor(&cells, region, &Rule::parse("B/S0").unwrap(), Boundary::Void), Ok(Predecessor::Found(_))));
+        assert_eq!(find_predecessor(&cells, region, &Rule::parse("B/S").unwrap(), Boundary::Void), Ok(Predecessor::None));
+        assert!(find_predecessor(&cells, region, &Rule::parse("B2/S/C3").unwrap(), Boundary::Void).is_err());
+        assert!(find_predecessor(&cells, region, &Rule::parse("B/S0").unwrap(), Boundary::Wrap).is_err());
+        let beyond = Region { row: 3, col: 3, rows: 4, cols: 4 };
+        assert!(find_predecessor(&cells, beyond, &Rule::parse("B/S0").unwrap(), Boundary::Void).is_err());
+    }
+
+    #[test]
+    fn gardens_of_eden_have_no_predecessor() {
+        // no arrangement of cells becomes this under B3/S23, which was checked separately by
+        // following every pair of parent columns that fits the target so far
+        let pattern: [&str; 6] = [
+            "o.oooooo.ooooo.ooo.o.ooo.ooooo.",
+            ".oooo.oooo.o..o.o.ooo..o.o.oo..",
+            "o.o.ooo.ooo.o.ooooo..oooo.o..oo",
+            ".o..o.ooooo..ooo.ooo.o..ooo.oo.",
+            ".ooo.ooo.ooo.o.ooo.oooo.o.ooo..",
+            "oo.ooo.ooo.oooo.o.ooo.oooo..oo.",
+        ];
+        let mut cells: Grid = vec![vec![0; 40]; 10];
+        for (i, line) in pattern.iter().enumerate() {
+            for (j, cell) in line.chars().enumerate() {
+                cells[2 + i][2 + j] = (cell == 'o') as u8;
+            }
+        }
+        let region = Region { row: 2, col: 2, rows: 6, cols: 31 };
+        assert_eq!(find_predecessor(&cells, region, &Rule::default(), Boundary::Void), Ok(Predecessor::None));
+    }
+}
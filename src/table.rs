@@ -0,0 +1,554 @@
+use crate::config::Config;
+use crate::hensel::NEIGHBOURS;
+use crate::rule::{Neighbourhood, MAX_STATES};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// most transitions a table may expand into once its bound variables are given every value
+const MAX_TRANSITIONS: usize = 1_000_000;
+
+// most neighbourhoods to remember the next state of, after which they are forgotten and found again
+const MAX_LOOKUP: usize = 1 << 20;
+
+/// Golly's Wireworld rule, where 1 is an electron head, 2 an electron tail and 3 a conductor
+const WIREWORLD: &str = "@RULE WireWorld
+
+@TABLE
+n_states:4
+neighborhood:Moore
+symmetries:permute
+var a={0,1,2,3}
+var b={0,1,2,3}
+var c={0,1,2,3}
+var d={0,1,2,3}
+var e={0,1,2,3}
+var f={0,1,2,3}
+var g={0,1,2,3}
+var h={0,1,2,3}
+var i={0,2,3}
+var j={0,2,3}
+var k={0,2,3}
+var l={0,2,3}
+var m={0,2,3}
+var n={0,2,3}
+var o={0,2,3}
+# electron heads become tails, and tails become conductors
+1,a,b,c,d,e,f,g,h,2
+2,a,b,c,d,e,f,g,h,3
+# conductors with one or two neighbouring heads become heads
+3,1,i,j,k,l,m,n,o,1
+3,1,1,i,j,k,l,m,n,1
+
+@COLORS
+1 0 128 255
+2 255 255 255
+3 255 128 0
+";
+
+/// Rule tables built into the application, found by the name after @RULE
+const BUILT_IN: [&str; 1] = [WIREWORLD];
+
+/// Set of states, one bit each
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct StateSet([u64; 4]);
+
+impl StateSet {
+    fn new(states: &[u8]) -> Self {
+        let mut set = StateSet([0; 4]);
+        for &state in states {
+            set.0[state as usize / 64] |= 1 << (state % 64);
+        }
+        set
+    }
+
+    fn contains(&self, state: u8) -> bool {
+        self.0[state as usize / 64] & 1 << (state % 64) != 0
+    }
+}
+
+/// One line of a rule table, giving the next state of a cell whose state and neighbours' states
+/// are in the sets of inputs, in the order of the table's neighbourhood
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Transition {
+    inputs: Vec<StateSet>,
+    output: u8,
+}
+
+/// A rule given by a table of transitions in Golly's .rule format, for automata with any number
+/// of states
+/// The next state of each neighbourhood is looked up once and remembered, under the same key for
+/// every neighbourhood the symmetries make alike
+#[derive(Clone, Debug)]
+pub struct RuleTable {
+    pub name: String,
+    pub states: u8,
+    pub neighbourhood: Neighbourhood,
+    /// Color of each state given in the file, if any
+    pub colors: Vec<Option<(u8, u8, u8)>>,
+    /// Orders the neighbours may be read in, as the positions read for each input
+    symmetries: Vec<Vec<usize>>,
+    /// Whether the neighbours may be read in any order, rather than those of the symmetries
+    is_permuted: bool,
+    transitions: Vec<Transition>,
+    /// Positions of the transitions for each state of the cell, in the order of the table
+    by_center: Vec<Vec<usize>>,
+    /// Next state of each neighbourhood found so far, keyed by the cell's state followed by the
+    /// neighbours' states in the symmetries' smallest order
+    lookup: RefCell<HashMap<Vec<u8>, u8>>,
+}
+
+impl PartialEq for RuleTable {
+    fn eq(&self, other: &Self) -> bool {
+        // what has been looked up so far makes no difference to the rule
+        self.name == other.name && self.states == other.states && self.neighbourhood == other.neighbourhood
+            && self.colors == other.colors && self.symmetries == other.symmetries
+            && self.is_permuted == other.is_permuted && self.transitions == other.transitions
+    }
+}
+
+impl Eq for RuleTable {}
+
+/// A state or variable in a line of a rule table
+enum Token<'a> {
+    State(u8),
+    Var(&'a str),
+}
+
+impl RuleTable {
+    /// Parse a rule file with an @RULE name and an @TABLE section, and optionally @COLORS
+    /// Other sections, such as @ICONS, are ignored
+    pub fn parse(text: &str) -> Result<RuleTable, String> {
+        let mut table = RuleTable {
+            name: String::new(),
+            states: 0,
+            neighbourhood: Neighbourhood::Moore,
+            colors: Vec::new(),
+            symmetries: Vec::new(),
+            is_permuted: false,
+            transitions: Vec::new(),
+            by_center: Vec::new(),
+            lookup: RefCell::new(HashMap::new()),
+        };
+        let mut symmetries = "none";
+        let mut vars: HashMap<&str, Vec<u8>> = HashMap::new();
+        let mut lines: Vec<&str> = Vec::new();
+        let mut colors: Vec<&str> = Vec::new();
+        let mut section = "";
+
+        // sort the lines into their sections, reading the settings of the table as they come
+        for line in text.lines() {
+            let line: &str = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('@') {
+                let (keyword, rest) = header.split_once(char::is_whitespace).unwrap_or((header, ""));
+                section = keyword;
+                if section == "RULE" {
+                    table.name = rest.trim().to_string();
+                }
+                continue;
+            }
+
+            match section {
+                "TABLE" => {
+                    if let Some((key, value)) = line.split_once(':') {
+                        let value: &str = value.trim();
+                        match key.trim() {
+                            "n_states" => table.states = match value.parse::<u32>() {
+                                Ok(n) if (2..=MAX_STATES).contains(&n) => n as u8,
+                                Ok(n) if n > MAX_STATES => return Err(format!("Rule tables can have at most {} states, not {}", MAX_STATES, n)),
+                                _ => return Err(format!("Invalid number of states {} in rule table", value)),
+                            },
+                            "neighborhood" => table.neighbourhood = match value.to_lowercase().as_str() {
+                                "moore" => Neighbourhood::Moore,
+                                "vonneumann" => Neighbourhood::VonNeumann,
+                                "hexagonal" => Neighbourhood::Hexagonal,
+                                _ => return Err(format!("Unsupported neighborhood {} in rule table", value)),
+                            },
+                            "symmetries" => symmetries = value,
+                            _ => return Err(format!("Invalid setting {} in rule table", key.trim())),
+                        }
+                    } else if let Some(var) = line.strip_prefix("var ") {
+                        let (name, values) = parse_var(var, &vars, table.states)?;
+                        vars.insert(name, values);
+                    } else {
+                        lines.push(line);
+                    }
+                },
+                "COLORS" => colors.push(line),
+                "" => return Err("Expected @RULE at the start of the rule table".to_string()),
+                _ => {},
+            }
+        }
+        if table.name.is_empty() {
+            return Err("Expected a name after @RULE in the rule table".to_string());
+        }
+        if table.states == 0 {
+            return Err("Expected n_states in the @TABLE section of the rule table".to_string());
+        }
+
+        // the neighbours are read clockwise from the north, so symmetries turn or mirror them
+        let count: usize = table.neighbourhood.mask().count_ones() as usize;
+        table.is_permuted = symmetries == "permute";
+        table.symmetries = if table.is_permuted {
+            Vec::new()
+        } else {
+            parse_symmetries(symmetries, count)?
+        };
+
+        for line in lines {
+            table.transitions.extend(parse_transition(line, &vars, table.states, count)?);
+            if table.transitions.len() > MAX_TRANSITIONS {
+                return Err("Rule table has too many transitions once its variables are expanded".to_string());
+            }
+        }
+
+        table.by_center = vec![Vec::new(); table.states as usize];
+        for (index, transition) in table.transitions.iter().enumerate() {
+            for center in 0..table.states {
+                if transition.inputs[0].contains(center) {
+                    table.by_center[center as usize].push(index);
+                }
+            }
+        }
+
+        table.colors = vec![None; table.states as usize];
+        for line in colors {
+            let numbers: Vec<u8> = line.split_whitespace().filter_map(|n| n.parse().ok()).collect();
+            match numbers[..] {
+                [state, r, g, b] if state < table.states => table.colors[state as usize] = Some((r, g, b)),
+                // a gradient from the first color for state 1 to the second for the last state
+                [r1, g1, b1, r2, g2, b2] => {
+                    let last: f32 = (table.states as f32 - 2.0).max(1.0);
+                    let mix = |from: u8, to: u8, t: f32| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+                    for state in 1..table.states {
+                        let t: f32 = (state - 1) as f32 / last;
+                        table.colors[state as usize] = Some((mix(r1, r2, t), mix(g1, g2, t), mix(b1, b2, t)));
+                    }
+                },
+                _ => eprintln!("WARNING: Ignoring color {} in rule table", line),
+            }
+        }
+
+        Ok(table)
+    }
+
+    /// Get the offsets of the neighbours as (row, col), in the order they are read by the table
+    pub fn offsets(&self) -> Vec<(isize, isize)> {
+        let mask: u8 = self.neighbourhood.mask();
+        (0..8).filter(|bit| mask & 1 << bit != 0).map(|bit| NEIGHBOURS[bit]).collect()
+    }
+
+    /// Given the state of a cell followed by the states of its neighbours, find its next state
+    /// from the first transition that matches, staying the same if none do
+    pub fn next_state(&self, states: &[u8]) -> u8 {
+        let key: Vec<u8> = self.canonical(states);
+        if let Some(&next) = self.lookup.borrow().get(&key) {
+            return next;
+        }
+        let next: u8 = self.search(&key);
+        let mut lookup = self.lookup.borrow_mut();
+        if lookup.len() >= MAX_LOOKUP {
+            lookup.clear();
+        }
+        lookup.insert(key, next);
+        next
+    }
+
+    /// Get the state of a cell followed by the states of its neighbours in the smallest order
+    /// the symmetries allow, which is sorted when they may be read in any order
+    fn canonical(&self, states: &[u8]) -> Vec<u8> {
+        let (center, neighbours) = (states[0], &states[1..]);
+        let ordered: Vec<u8> = if self.is_permuted {
+            let mut sorted: Vec<u8> = neighbours.to_vec();
+            sorted.sort_unstable();
+            sorted
+        } else {
+            self.symmetries.iter()
+                .map(|order| order.iter().map(|&position| neighbours[position]).collect::<Vec<u8>>())
+                .min()
+                .unwrap_or_else(|| neighbours.to_vec())
+        };
+        [&[center], ordered.as_slice()].concat()
+    }
+
+    /// Find the next state from the transitions for the cell's state, in the order of the table
+    fn search(&self, states: &[u8]) -> u8 {
+        let (center, neighbours) = (states[0], &states[1..]);
+        for &index in &self.by_center[center as usize] {
+            let transition: &Transition = &self.transitions[index];
+            let inputs: &[StateSet] = &transition.inputs[1..];
+            let is_match: bool = if self.is_permuted {
+                matches_any_order(inputs, neighbours, 0)
+            } else {
+                self.symmetries.iter().any(|order| {
+                    order.iter().zip(inputs).all(|(&position, input)| input.contains(neighbours[position]))
+                })
+            };
+            if is_match {
+                return transition.output;
+            }
+        }
+        center
+    }
+}
+
+/// Check whether each set of inputs can be matched with a different one of the states, skipping
+/// the states already used, marked one bit each
+fn matches_any_order(inputs: &[StateSet], states: &[u8], used: u16) -> bool {
+    match inputs.split_first() {
+        None => true,
+        Some((input, rest)) => (0..states.len()).any(|k| {
+            used & 1 << k == 0 && input.contains(states[k]) && matches_any_order(rest, states, used | 1 << k)
+        }),
+    }
+}
+
+/// Parse a variable such as "a={0,1,b}" into its name and values, where other variables stand
+/// for all of their values
+fn parse_var<'a>(
+    text: &'a str,
+    vars: &HashMap<&str, Vec<u8>>,
+    states: u8,
+) -> Result<(&'a str, Vec<u8>), String> {
+    let (name, values) = match text.split_once('=') {
+        Some((name, values)) => (name.trim(), values.trim()),
+        None => return Err(format!("Invalid variable {} in rule table", text)),
+    };
+    let values: &str = match values.strip_prefix('{').and_then(|v| v.strip_suffix('}')) {
+        Some(v) => v,
+        None => return Err(format!("Expected values of variable {} in braces", name)),
+    };
+
+    let mut states_of_var: Vec<u8> = Vec::new();
+    for value in values.split(',').map(|v| v.trim()) {
+        match parse_token(value, vars, states)? {
+            Token::State(state) => states_of_var.push(state),
+            Token::Var(other) => states_of_var.extend(&vars[other]),
+        }
+    }
+    Ok((name, states_of_var))
+}
+
+/// Parse a state or the name of a variable that has been defined
+fn parse_token<'a>(text: &'a str, vars: &HashMap<&str, Vec<u8>>, states: u8) -> Result<Token<'a>, String> {
+    if let Ok(state) = text.parse::<u32>() {
+        if state >= states as u32 {
+            return Err(format!("Invalid state {} in rule table with {} states", state, states));
+        }
+        Ok(Token::State(state as u8))
+    } else if vars.contains_key(text) {
+        Ok(Token::Var(text))
+    } else {
+        Err(format!("Unknown variable {} in rule table", text))
+    }
+}
+
+/// Parse the symmetries of a table into the orders its neighbours may be read in, where each
+/// rotation moves the neighbours around the cell and each reflection mirrors them left to right
+fn parse_symmetries(text: &str, count: usize) -> Result<Vec<Vec<usize>>, String> {
+    let (turns, is_reflected): (usize, bool) = match text {
+        "none" => (1, false),
+        "reflect" | "reflect_horizontal" => (1, true),
+        _ => {
+            let rotations: &str = text.strip_prefix("rotate").unwrap_or("");
+            let (turns, is_reflected) = match rotations.strip_suffix("reflect") {
+                Some(turns) => (turns, true),
+                None => (rotations, false),
+            };
+            match turns.parse::<usize>() {
+                Ok(turns) if turns > 1 && count.is_multiple_of(turns) => (turns, is_reflected),
+                _ => return Err(format!("Unsupported symmetries {} in rule table", text)),
+            }
+        },
+    };
+
+    let step: usize = count / turns;
+    let mut orders: Vec<Vec<usize>> = Vec::new();
+    for turn in 0..turns {
+        let rotated: Vec<usize> = (0..count).map(|i| (i + turn * step) % count).collect();
+        if is_reflected {
+            orders.push(rotated.iter().map(|&i| (count - i) % count).collect());
+        }
+        orders.push(rotated);
+    }
+    Ok(orders)
+}
+
+/// Parse a line of the table into transitions, one for each combination of values of the
+/// variables that appear more than once, as those must take the same value everywhere they appear
+/// Lines may separate their states with commas, or leave them out if every state is one character
+fn parse_transition(
+    line: &str,
+    vars: &HashMap<&str, Vec<u8>>,
+    states: u8,
+    count: usize,
+) -> Result<Vec<Transition>, String> {
+    let texts: Vec<&str> = if line.contains(',') {
+        line.split(',').map(|t| t.trim()).collect()
+    } else {
+        line.char_indices().map(|(i, c)| &line[i..i + c.len_utf8()]).collect()
+    };
+    if texts.len() != count + 2 {
+        return Err(format!("Invalid transition {} in rule table, expected {} states", line, count + 2));
+    }
+    let tokens: Vec<Token> = texts.iter().map(|t| parse_token(t, vars, states)).collect::<Result<_, _>>()?;
+
+    // find the variables that are bound, keeping the order they first appear in
+    let mut bound: Vec<&str> = Vec::new();
+    for token in &tokens {
+        if let Token::Var(name) = token {
+            let uses: usize = tokens.iter().filter(|t| matches!(t, Token::Var(n) if n == name)).count();
+            if uses > 1 && !bound.contains(name) {
+                bound.push(name);
+            }
+        }
+    }
+    let mut combinations: Vec<Vec<u8>> = vec![Vec::new()];
+    for name in &bound {
+        combinations = combinations.iter()
+            .flat_map(|values| vars[name].iter().map(move |&v| [values.as_slice(), &[v]].concat()))
+            .collect();
+        if combinations.len() > MAX_TRANSITIONS {
+            return Err("Rule table has too many transitions once its variables are expanded".to_string());
+        }
+    }
+
+    let mut transitions: Vec<Transition> = Vec::new();
+    for values in combinations {
+        let value_of = |name: &str| bound.iter().position(|b| *b == name).map(|i| values[i]);
+        let inputs: Vec<StateSet> = tokens[..count + 1].iter().map(|token| match token {
+            Token::State(state) => StateSet::new(&[*state]),
+            Token::Var(name) => match value_of(name) {
+                Some(value) => StateSet::new(&[value]),
+                None => StateSet::new(&vars[name]),
+            },
+        }).collect();
+        let output: u8 = match &tokens[count + 1] {
+            Token::State(state) => *state,
+            Token::Var(name) => match value_of(name) {
+                Some(value) => value,
+                None => return Err(format!("Output {} of transition {} does not appear in its inputs", name, line)),
+            },
+        };
+        transitions.push(Transition { inputs, output });
+    }
+    Ok(transitions)
+}
+
+/// Find a rule table by name, ignoring case, among the built-in tables and the .rule files in the
+/// rules directory next to the config file
+/// Returns None if there is no table of that name
+pub fn find(name: &str) -> Result<Option<RuleTable>, String> {
+    for text in BUILT_IN {
+        let table: RuleTable = RuleTable::parse(text)?;
+        if table.name.eq_ignore_ascii_case(name) {
+            return Ok(Some(table));
+        }
+    }
+
+    let directory = match Config::rules_dir() {
+        Some(d) => d,
+        None => return Ok(None),
+    };
+    let entries = match fs::read_dir(&directory) {
+        Ok(e) => e,
+        Err(_) => return Ok(None),
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_match: bool = path.file_stem().is_some_and(|s| s.to_string_lossy().eq_ignore_ascii_case(name))
+            && path.extension().is_some_and(|e| e.eq_ignore_ascii_case("rule"));
+        if is_match {
+            return load(&path).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// Load a rule table from a .rule file
+pub fn load(path: &Path) -> Result<RuleTable, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => RuleTable::parse(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) => Err(format!("Could not read {}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wireworld_moves_electrons_along_wires() {
+        let table: RuleTable = find("wireworld").unwrap().unwrap();
+        assert_eq!(table.colors[3], Some((255, 128, 0)));
+
+        // a conductor next to one head becomes a head, but not next to three
+        assert_eq!(table.next_state(&[3, 0, 0, 0, 0, 0, 0, 1, 0]), 1);
+        assert_eq!(table.next_state(&[3, 1, 0, 1, 0, 1, 0, 0, 0]), 3);
+        assert_eq!(table.next_state(&[1, 0, 0, 0, 0, 0, 0, 0, 0]), 2);
+        assert_eq!(table.next_state(&[2, 0, 0, 0, 0, 0, 0, 0, 0]), 3);
+        assert_eq!(table.next_state(&[0, 1, 1, 1, 0, 0, 0, 0, 0]), 0);
+    }
+
+    #[test]
+    fn bound_variables_and_symmetries() {
+        let text = "@RULE Test
+@TABLE
+n_states:3
+neighborhood:vonNeumann
+symmetries:rotate4
+var a={1,2}
+# a cell copies the state to its north when the state to its east is the same
+0,a,a,0,0,a
+012002";
+        let table: RuleTable = RuleTable::parse(text).unwrap();
+        assert_eq!(table.next_state(&[0, 2, 2, 0, 0]), 2);
+        assert_eq!(table.next_state(&[0, 2, 1, 0, 0]), 0);
+
+        // rotations of the neighbours match too, but not reflections, and lines may leave out
+        // the commas
+        assert_eq!(table.next_state(&[0, 0, 1, 1, 0]), 1);
+        assert_eq!(table.next_state(&[0, 0, 0, 1, 2]), 2);
+        assert_eq!(table.next_state(&[0, 2, 0, 0, 1]), 2);
+        assert_eq!(table.next_state(&[0, 1, 0, 0, 2]), 0);
+
+        assert!(RuleTable::parse("@RULE Bad\n@TABLE\nn_states:2\nneighborhood:Moore\n0,0,0,0,0,0,0,0,0,b").is_err());
+    }
+
+    #[test]
+    fn states_are_limited_to_a_byte() {
+        let table = |states: u32| RuleTable::parse(&format!("@RULE Many\n@TABLE\nn_states:{}\nneighborhood:Moore\n", states));
+        assert_eq!(table(255).unwrap().states, 255);
+        assert!(table(256).is_err());
+        assert!(table(1).is_err());
+    }
+
+    #[test]
+    fn alike_neighbourhoods_share_a_lookup() {
+        let table: RuleTable = find("wireworld").unwrap().unwrap();
+        assert_eq!(table.next_state(&[3, 1, 0, 0, 0, 0, 0, 0, 0]), 1);
+        assert_eq!(table.next_state(&[3, 0, 0, 0, 0, 0, 1, 0, 0]), 1);
+        assert_eq!(table.next_state(&[3, 0, 0, 3, 0, 0, 1, 0, 0]), 1);
+        assert_eq!(table.lookup.borrow().len(), 2);
+    }
+
+    #[test]
+    fn colors_may_be_a_gradient() {
+        let text = "@RULE Fade
+@TABLE
+n_states:4
+neighborhood:vonNeumann
+0,1,0,0,0,1
+@COLORS
+0 255 255 255 0 0
+3 1 2 3
+unknown";
+        let table: RuleTable = RuleTable::parse(text).unwrap();
+        assert_eq!(table.colors, [None, Some((0, 255, 255)), Some((128, 128, 128)), Some((1, 2, 3))]);
+    }
+}
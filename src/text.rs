@@ -9,7 +9,8 @@ use std::collections::HashMap;
 pub struct TextCache<'creator, 'font> {
     texture_creator: &'creator TextureCreator<WindowContext>,
     font: &'font Font<'font, 'static>,
-    textures: HashMap<String, Texture<'creator>>,
+    // each texture is marked when drawn, so that text no longer shown can be let go
+    textures: HashMap<(String, Color), (Texture<'creator>, bool)>,
 }
 
 impl<'creator, 'font> TextCache<'creator, 'font> {
@@ -24,21 +25,31 @@ impl<'creator, 'font> TextCache<'creator, 'font> {
         }
     }
 
-    /// Render text by getting the texture from the hashmap, or creating a new one
-    pub fn render_text(&mut self, text: &str) -> &Texture<'creator> {
-        if self.textures.contains_key(text) {
-            return &self.textures[text];
-        } else {
+    /// Render text in the given color by getting the texture from the hashmap, or creating a new one
+    pub fn render_text(&mut self, text: &str, color: Color) -> &Texture<'creator> {
+        let key = (text.to_string(), color);
+        if !self.textures.contains_key(&key) {
             let text_surface: Surface = self.font
                 .render(text)
-                .blended(Color::BLACK)
+                .blended(color)
                 .expect("Failed to render text");
             let text_texture: Texture = self
                 .texture_creator
                 .create_texture_from_surface(text_surface)
                 .unwrap();
-            self.textures.insert(text.to_string(), text_texture);
-            return &self.textures[text];
+            self.textures.insert(key.clone(), (text_texture, true));
+        }
+        let (texture, is_used) = self.textures.get_mut(&key).unwrap();
+        *is_used = true;
+        texture
+    }
+
+    /// Drop the textures of text that was not drawn since this was last called, which keeps
+    /// text that changes every frame, such as the status line, from piling up
+    pub fn evict_unused(&mut self) {
+        self.textures.retain(|_, (_, is_used)| *is_used);
+        for (_, is_used) in self.textures.values_mut() {
+            *is_used = false;
         }
     }
 
@@ -0,0 +1,85 @@
+use sdl2::pixels::Color;
+
+/// Set of colours used to render the application
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    pub background: Color,
+    pub grid: Color,
+    pub cell: Color,
+    pub cell_new: Color,
+    pub trail: Color,
+    pub history: Color,
+    pub selection: Color,
+    pub text: Color,
+    pub control: Color,
+    pub control_fill: Color,
+}
+
+pub const LIGHT: Theme = Theme {
+    name: "light",
+    background: Color::WHITE,
+    grid: Color::BLACK,
+    cell: Color::GRAY,
+    cell_new: Color::RGB(224, 64, 32),
+    trail: Color::RGB(96, 144, 255),
+    history: Color::RGB(208, 232, 208),
+    selection: Color::RGB(32, 96, 224),
+    text: Color::BLACK,
+    control: Color::BLACK,
+    control_fill: Color::RGB(192, 192, 192),
+};
+
+pub const DARK: Theme = Theme {
+    name: "dark",
+    background: Color::RGB(24, 24, 28),
+    grid: Color::RGB(64, 64, 72),
+    cell: Color::RGB(224, 224, 232),
+    cell_new: Color::RGB(255, 144, 64),
+    trail: Color::RGB(48, 96, 208),
+    history: Color::RGB(40, 64, 48),
+    selection: Color::RGB(96, 160, 255),
+    text: Color::RGB(224, 224, 232),
+    control: Color::RGB(224, 224, 232),
+    control_fill: Color::RGB(80, 80, 96),
+};
+
+pub const HIGH_CONTRAST: Theme = Theme {
+    name: "contrast",
+    background: Color::BLACK,
+    grid: Color::RGB(96, 96, 96),
+    cell: Color::YELLOW,
+    cell_new: Color::MAGENTA,
+    trail: Color::CYAN,
+    history: Color::RGB(0, 96, 0),
+    selection: Color::RGB(255, 128, 0),
+    text: Color::WHITE,
+    control: Color::WHITE,
+    control_fill: Color::RGB(0, 64, 192),
+};
+
+/// All built-in themes, in the order they are cycled through
+pub const THEMES: [Theme; 3] = [LIGHT, DARK, HIGH_CONTRAST];
+
+impl Theme {
+    /// Find a built-in theme by name, ignoring case
+    pub fn from_name(name: &str) -> Option<Theme> {
+        THEMES.iter().find(|t| t.name.eq_ignore_ascii_case(name)).copied()
+    }
+
+    /// Get the theme after this one in the cycle of built-in themes
+    pub fn next(&self) -> Theme {
+        let index = THEMES.iter().position(|t| t == self).unwrap_or(0);
+        THEMES[(index + 1) % THEMES.len()]
+    }
+}
+
+/// Linearly interpolate between two colors, where t = 0 gives the first and t = 1 the second
+pub fn mix(from: Color, to: Color, t: f32) -> Color {
+    let t: f32 = t.clamp(0.0, 1.0);
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color::RGBA(channel(from.r, to.r),
+                channel(from.g, to.g),
+                channel(from.b, to.b),
+                channel(from.a, to.a))
+}
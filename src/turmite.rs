@@ -0,0 +1,255 @@
+use crate::boundary::Boundary;
+use crate::life::Grid;
+
+/// Direction an ant is facing
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Direction {
+    /// Get the direction after making the given turn
+    fn turn(self, turn: Turn) -> Direction {
+        const CLOCKWISE: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+        let index = CLOCKWISE.iter().position(|&d| d == self).unwrap_or(0);
+        let quarters: usize = match turn {
+            Turn::None => 0,
+            Turn::Right => 1,
+            Turn::Back => 2,
+            Turn::Left => 3,
+        };
+        CLOCKWISE[(index + quarters) % 4]
+    }
+
+    /// Get the step to the next cell in this direction as (row, col)
+    pub fn offset(self) -> (isize, isize) {
+        match self {
+            Direction::North => (-1, 0),
+            Direction::East => (0, 1),
+            Direction::South => (1, 0),
+            Direction::West => (0, -1),
+        }
+    }
+}
+
+/// Turn an ant makes before stepping forward, numbered as in Golly's turmite notation
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Turn {
+    None = 1,
+    Right = 2,
+    Back = 4,
+    Left = 8,
+}
+
+impl Turn {
+    /// Get the turn written as a letter in ant rules such as RL, or None if it is not one
+    fn from_letter(letter: char) -> Option<Turn> {
+        match letter {
+            'N' => Some(Turn::None),
+            'R' => Some(Turn::Right),
+            'U' => Some(Turn::Back),
+            'L' => Some(Turn::Left),
+            _ => None,
+        }
+    }
+
+    /// Get the turn with the given number in Golly's turmite notation
+    fn from_number(number: u32) -> Option<Turn> {
+        [Turn::None, Turn::Right, Turn::Back, Turn::Left].into_iter().find(|&t| t as u32 == number)
+    }
+}
+
+/// An ant walking over the grid, with the state it is in under a turmite rule
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Ant {
+    pub row: usize,
+    pub col: usize,
+    pub direction: Direction,
+    pub state: u8,
+}
+
+impl Ant {
+    /// Make an ant in its first state facing north
+    pub fn new(row: usize, col: usize) -> Ant {
+        Ant { row, col, direction: Direction::North, state: 0 }
+    }
+}
+
+/// A turmite rule, giving for each state of an ant and color of the cell it is on the color to
+/// paint the cell, the turn to make and the ant's next state
+/// Langton's ant and its relatives have a single state and are written as a turn for each color
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Turmite {
+    pub colors: u8,
+    transitions: Vec<Vec<(u8, Turn, u8)>>,
+}
+
+impl Turmite {
+    /// Parse an ant rule written as a letter for each color, such as RL for Langton's ant, where
+    /// R and L turn right and left, N goes straight on and U turns back, or a turmite in Golly's
+    /// notation, such as {{{1,2,0},{0,8,0}}}, giving the color, turn and state for each state and
+    /// color, where turns are 1 for none, 2 for right, 4 for back and 8 for left
+    /// Returns None if the text is not written as either
+    pub fn parse(text: &str) -> Option<Result<Turmite, String>> {
+        if text.starts_with('{') {
+            return Some(Turmite::parse_table(text));
+        }
+        let turns: Vec<Turn> = text.chars().map(Turn::from_letter).collect::<Option<_>>()?;
+        if turns.len() < 2 || turns.len() > u8::MAX as usize {
+            return Some(Err(format!("Invalid ant rule {}, expected 2 to {} turns", text, u8::MAX)));
+        }
+
+        // each color is painted over with the next one, in a cycle
+        let colors = turns.len() as u8;
+        let transitions: Vec<(u8, Turn, u8)> = turns.iter().enumerate()
+            .map(|(color, &turn)| (((color + 1) % turns.len()) as u8, turn, 0))
+            .collect();
+        Some(Ok(Turmite { colors, transitions: vec![transitions] }))
+    }
+
+    /// Parse a turmite in Golly's notation, with a list of colors for each state and a list of
+    /// three numbers for each color
+    fn parse_table(text: &str) -> Result<Turmite, String> {
+        let invalid = || format!("Invalid turmite {}, expected the form {{{{{{1,2,0}},{{0,8,0}}}}}}", text);
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        let inner: &str = text.strip_prefix("{{{").and_then(|t| t.strip_suffix("}}}")).ok_or_else(invalid)?;
+
+        let mut numbers: Vec<Vec<Vec<u32>>> = Vec::new();
+        for state in inner.split("}},{{") {
+            let mut colors: Vec<Vec<u32>> = Vec::new();
+            for color in state.split("},{") {
+                let triple: Vec<u32> = color.split(',').map(|n| n.parse().ok()).collect::<Option<_>>().ok_or_else(invalid)?;
+                if triple.len() != 3 {
+                    return Err(invalid());
+                }
+                colors.push(triple);
+            }
+            numbers.push(colors);
+        }
+
+        let colors: usize = numbers[0].len();
+        if colors < 2 || colors > u8::MAX as usize || numbers.iter().any(|state| state.len() != colors) {
+            return Err(format!("Invalid turmite {}, every state needs the same number of colors, at least 2", text));
+        }
+        if numbers.len() > u8::MAX as usize {
+            return Err(format!("Invalid turmite {}, it has too many states", text));
+        }
+
+        let mut transitions: Vec<Vec<(u8, Turn, u8)>> = Vec::new();
+        for state in &numbers {
+            let mut row: Vec<(u8, Turn, u8)> = Vec::new();
+            for triple in state {
+                let (color, turn, next) = (triple[0], triple[1], triple[2]);
+                if color as usize >= colors || next as usize >= numbers.len() {
+                    return Err(format!("Invalid turmite {}, {{{},{},{}}} names a missing color or state", text, color, turn, next));
+                }
+                let turn: Turn = Turn::from_number(turn)
+                    .ok_or_else(|| format!("Invalid turn {} in turmite, expected 1, 2, 4 or 8", turn))?;
+                row.push((color as u8, turn, next as u8));
+            }
+            transitions.push(row);
+        }
+        Ok(Turmite { colors: colors as u8, transitions })
+    }
+
+    /// Move every ant one step in turn, each painting its cell and turning as the rule says for
+    /// its state and the color of the cell, then stepping forward
+    /// Ants keep their direction when crossing an edge, and are lost beyond an edge where
+    /// everything is dead
+    pub fn step(&self, cells: &mut Grid, ants: &mut Vec<Ant>, boundary: Boundary) {
+        let rows = cells.len();
+        let cols = cells[0].len();
+        ants.retain_mut(|ant| {
+            let color: usize = (cells[ant.row][ant.col] % self.colors) as usize;
+            let (paint, turn, state) = self.transitions[ant.state as usize][color];
+            cells[ant.row][ant.col] = paint;
+            ant.direction = ant.direction.turn(turn);
+            ant.state = state;
+
+            let (di, dj) = ant.direction.offset();
+            match boundary.resolve(ant.row as isize + di, ant.col as isize + dj, rows, cols) {
+                Some((row, col)) => {
+                    (ant.row, ant.col) = (row, col);
+                    true
+                },
+                None => false,
+            }
+        });
+    }
+}
+
+impl std::fmt::Display for Turmite {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // ants that paint each color with the next are written as letters
+        let is_ant: bool = self.transitions.len() == 1
+            && self.transitions[0].iter().enumerate().all(|(color, t)| t.0 as usize == (color + 1) % self.colors as usize);
+        if is_ant {
+            for (_, turn, _) in &self.transitions[0] {
+                let letter: char = match turn {
+                    Turn::None => 'N',
+                    Turn::Right => 'R',
+                    Turn::Back => 'U',
+                    Turn::Left => 'L',
+                };
+                write!(f, "{}", letter)?;
+            }
+            return Ok(());
+        }
+
+        let states: Vec<String> = self.transitions.iter().map(|state| {
+            let colors: Vec<String> = state.iter()
+                .map(|(color, turn, next)| format!("{{{},{},{}}}", color, *turn as u32, next))
+                .collect();
+            format!("{{{}}}", colors.join(","))
+        }).collect();
+        write!(f, "{{{}}}", states.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn langtons_ant_walks_a_square_then_turns_back() {
+        let turmite: Turmite = Turmite::parse("RL").unwrap().unwrap();
+        let mut cells: Grid = vec![vec![0; 5]; 5];
+        let mut ants: Vec<Ant> = vec![Ant::new(2, 2)];
+
+        // on blank cells the ant turns right each time, painting a square
+        for _ in 0..4 {
+            turmite.step(&mut cells, &mut ants, Boundary::Void);
+        }
+        assert_eq!(ants[0], Ant { row: 2, col: 2, direction: Direction::North, state: 0 });
+        assert_eq!(cells[2][2..4], [1, 1]);
+        assert_eq!(cells[3][2..4], [1, 1]);
+
+        // then it finds a painted cell and turns left, clearing it
+        turmite.step(&mut cells, &mut ants, Boundary::Void);
+        assert_eq!((ants[0].row, ants[0].col, ants[0].direction), (2, 1, Direction::West));
+        assert_eq!(cells[2][2], 0);
+
+        // ants walking off the edge of a void world are lost
+        let mut ants: Vec<Ant> = vec![Ant::new(0, 0)];
+        turmite.step(&mut vec![vec![1; 2]; 2], &mut ants, Boundary::Void);
+        assert!(ants.is_empty());
+    }
+
+    #[test]
+    fn notations_parse_and_print() {
+        let table: Turmite = Turmite::parse("{{{1, 2, 0}, {0, 8, 0}}}").unwrap().unwrap();
+        assert_eq!(table, Turmite::parse("RL").unwrap().unwrap());
+        assert_eq!(table.to_string(), "RL");
+
+        let text = "{{{1,2,1},{0,8,0}},{{1,1,0},{1,4,1}}}";
+        assert_eq!(Turmite::parse(text).unwrap().unwrap().to_string(), text);
+
+        assert!(Turmite::parse("B3/S23").is_none());
+        assert!(Turmite::parse("R").unwrap().is_err());
+        assert!(Turmite::parse("{{{1,3,0},{0,8,0}}}").unwrap().is_err());
+        assert!(Turmite::parse("{{{1,2,1},{0,8,0}}}").unwrap().is_err());
+    }
+}
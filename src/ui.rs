@@ -1,13 +1,155 @@
-use sdl2::pixels::Color;
+use serde::{Deserialize, Serialize};
+
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::{Point, Rect};
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use sdl2::render::{Canvas, Texture, TextureAccess, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+
+use crate::life::{History, Region};
+use crate::rule::Rule;
+use crate::theme::{mix, Theme};
+use crate::turmite::Ant;
 
 use std::cmp::min;
 
-const CELL_PADDING: i32 = 1;
 pub const BUFFER_SIZE: i32 = 60;
 
+// grid lines fade in over this many pixels of cell size above the fade size
+const GRID_FADE_RANGE: i32 = 4;
+
+// number of cells between major grid lines, and their thickness
+const MAJOR_LINE_SPACING: usize = 10;
+const MAJOR_LINE_WIDTH: u32 = 2;
+
+// opacity of the fill drawn over selected cells
+const SELECTION_ALPHA: u8 = 64;
+
+// number of generations over which cell colors change when colored by age or trail
+const AGE_SPAN: u32 = 32;
+const TRAIL_LENGTH: u32 = 16;
+
+/// Which grid lines are drawn over the cells
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GridLines {
+    Hidden,
+    Minor,
+    Major,
+}
+
+impl GridLines {
+    /// Get the next option when cycling through grid lines
+    pub fn next(&self) -> GridLines {
+        match self {
+            GridLines::Hidden => GridLines::Minor,
+            GridLines::Minor => GridLines::Major,
+            GridLines::Major => GridLines::Hidden,
+        }
+    }
+
+    /// Get the text shown on the button for this option
+    pub fn label(&self) -> &'static str {
+        match self {
+            GridLines::Hidden => "NO GRID",
+            GridLines::Minor => "GRID",
+            GridLines::Major => "GRID 10",
+        }
+    }
+}
+
+/// How cells are colored when rendering the cell field
+#[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ViewMode {
+    Plain,
+    Age,
+    Trail,
+    History,
+}
+
+impl ViewMode {
+    /// Get the next option when cycling through view modes
+    pub fn next(&self) -> ViewMode {
+        match self {
+            ViewMode::Plain => ViewMode::Age,
+            ViewMode::Age => ViewMode::Trail,
+            ViewMode::Trail => ViewMode::History,
+            ViewMode::History => ViewMode::Plain,
+        }
+    }
+
+    /// Get the text shown on the button for this option
+    pub fn label(&self) -> &'static str {
+        match self {
+            ViewMode::Plain => "PLAIN",
+            ViewMode::Age => "AGE",
+            ViewMode::Trail => "TRAIL",
+            ViewMode::History => "HISTORY",
+        }
+    }
+}
+
+/// Get the color of each state of a rule
+/// Decaying states fade from the trail color towards the background as they near death, while
+/// rule tables use their own colors, or Golly's gradient from red to yellow where they have none
+pub fn state_colors(theme: &Theme, rule: &Rule) -> Vec<Color> {
+    if let Some(table) = &rule.table {
+        let gradient: f32 = (table.states as f32 - 2.0).max(1.0);
+        let mut colors: Vec<Color> = vec![theme.background];
+        for state in 1..table.states {
+            colors.push(match table.colors[state as usize] {
+                Some((r, g, b)) => Color::RGB(r, g, b),
+                None => mix(Color::RGB(255, 0, 0), Color::RGB(255, 255, 0), (state - 1) as f32 / gradient),
+            });
+        }
+        return colors;
+    }
+
+    let mut colors: Vec<Color> = vec![theme.background, theme.cell];
+    for state in 2..rule.states {
+        colors.push(mix(theme.trail, theme.background, (state - 1) as f32 / rule.states as f32));
+    }
+    colors
+}
+
+/// Get the color of a cell in the given state and view mode, using its history and the colors of
+/// each state
+pub fn cell_color(
+    mode: ViewMode,
+    theme: &Theme,
+    history: &History,
+    palette: &[Color],
+    state: u8,
+    row: usize,
+    col: usize,
+) -> Color {
+    let age: u32 = history.age[row][col];
+    let dead_for: u32 = history.dead_for[row][col];
+
+    // decaying cells always show the color of their state
+    // a state beyond the palette, which the rule does not have, shows as the last one
+    if state > 1 {
+        return palette.get(state as usize).or(palette.last()).copied().unwrap_or(theme.cell);
+    }
+
+    // live cells fade from the newborn color to the usual color as they age
+    if state == 1 {
+        return match mode {
+            ViewMode::Age => mix(theme.cell_new, theme.cell, age.saturating_sub(1) as f32 / AGE_SPAN as f32),
+            _ => palette[1],
+        };
+    }
+
+    // dead cells may show a fading trail or whether they were ever alive
+    match mode {
+        ViewMode::Trail if dead_for <= TRAIL_LENGTH => {
+            mix(theme.trail, theme.background, dead_for as f32 / (TRAIL_LENGTH + 1) as f32)
+        },
+        ViewMode::History if history.ever_alive(row, col) => theme.history,
+        _ => palette[0],
+    }
+}
+
 /// Struct to convert between grid coordinates and screen coordinates
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Vector2 {
@@ -82,27 +224,106 @@ impl Cell {
     }
 }
 
-/// Given the canvas context and a Cell, render a cell within the grid
-pub fn render_cell(canvas: &mut Canvas<Window>, cell: Cell, cell_size: i32) {
-    canvas.set_draw_color(Color::GRAY);
-    let cell_start_x: i32 = cell.top_left.x + CELL_PADDING;
-    let cell_start_y: i32 = cell.top_left.y + CELL_PADDING;
-    let cell_dim: u32 = (cell_size - 2 * CELL_PADDING + 1) as u32;
-    let cell_rect = Rect::new(cell_start_x, cell_start_y, cell_dim, cell_dim);
-    canvas.fill_rect(cell_rect).unwrap();
+/// Get how far a row on screen is shifted to the right when cells are drawn on a hexagonal grid,
+/// where each row sits half a cell to the left of the row above, keeping the middle row in place
+pub fn hex_shift(row: i32, grid_rows: i32, cell_size: i32) -> i32 {
+    (grid_rows / 2 - row) * cell_size / 2
 }
 
-/// Given the canvas context, render the grid
+/// Get the area of the screen covered by the grid of the given dimensions, which everything drawn
+/// on a hexagonal grid is clipped to
+fn grid_area(cell_size: i32, grid_dim: (i32, i32)) -> Rect {
+    Rect::new(BUFFER_SIZE, BUFFER_SIZE, (grid_dim.1 * cell_size) as u32, (grid_dim.0 * cell_size) as u32)
+}
+
+/// Streaming texture holding one pixel per backend cell, scaled up to render the cell field
+pub struct CellField<'creator> {
+    texture: Texture<'creator>,
+}
+
+impl<'creator> CellField<'creator> {
+    pub fn new(
+        texture_creator: &'creator TextureCreator<WindowContext>,
+        rows: usize,
+        cols: usize,
+    ) -> Result<Self, String> {
+        // scale the texture with nearest filtering so that cells keep sharp edges
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "0");
+        let texture: Texture = texture_creator
+            .create_texture(PixelFormatEnum::RGBA32,
+                            TextureAccess::Streaming,
+                            cols as u32,
+                            rows as u32)
+            .map_err(|e| e.to_string())?;
+        Ok(CellField { texture })
+    }
+
+    /// Write the backend grid of the given size into the texture, coloring each cell
+    pub fn update(&mut self, rows: usize, cols: usize, color_of: impl Fn(usize, usize) -> Color) {
+        self.texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+            for i in 0..rows {
+                for j in 0..cols {
+                    let color: Color = color_of(i, j);
+                    let offset: usize = i * pitch + j * 4;
+                    buffer[offset..offset + 4].copy_from_slice(&[color.r, color.g, color.b, color.a]);
+                }
+            }
+        }).unwrap();
+    }
+
+    /// Render the part of the cell field that is on screen
+    /// The grid on screen has the given dimensions and starts at the given (x, y) backend cell
+    /// On a hexagonal grid each row is rendered on its own, shifted sideways
+    pub fn render(
+        &self,
+        canvas: &mut Canvas<Window>,
+        cell_size: i32,
+        grid_dim: (i32, i32),
+        cells_start: (usize, usize),
+        is_hex: bool,
+    ) {
+        if is_hex {
+            canvas.set_clip_rect(grid_area(cell_size, grid_dim));
+            for i in 0..grid_dim.0 {
+                let src = Rect::new(cells_start.0 as i32, cells_start.1 as i32 + i, grid_dim.1 as u32, 1);
+                let dst = Rect::new(BUFFER_SIZE + hex_shift(i, grid_dim.0, cell_size),
+                    BUFFER_SIZE + i * cell_size,
+                    (grid_dim.1 * cell_size) as u32,
+                    cell_size as u32);
+                canvas.copy(&self.texture, src, dst).unwrap();
+            }
+            canvas.set_clip_rect(None);
+            return;
+        }
+
+        let src = Rect::new(cells_start.0 as i32,
+            cells_start.1 as i32,
+            grid_dim.1 as u32,
+            grid_dim.0 as u32);
+        let dst = Rect::new(BUFFER_SIZE,
+            BUFFER_SIZE,
+            (grid_dim.1 * cell_size) as u32,
+            (grid_dim.0 * cell_size) as u32);
+        canvas.copy(&self.texture, src, dst).unwrap();
+    }
+}
+
+/// Get the opacity of grid lines for a cell size, which fade out as cells shrink to the fade size
+pub fn grid_alpha(cell_size: i32, fade_size: i32) -> u8 {
+    let visible: i32 = (cell_size - fade_size).clamp(0, GRID_FADE_RANGE);
+    (visible * 255 / GRID_FADE_RANGE) as u8
+}
+
+/// Given the canvas context, calculate how many rows and columns of cells fit on screen
 /// Returns a Result containing (rows, cols)
-pub fn render_grid(
-    canvas: &mut Canvas<Window>, 
+pub fn grid_dimensions(
+    canvas: &Canvas<Window>,
     cell_size: i32,
     max_rows: i32,
     max_cols: i32,
 ) -> Result<(i32, i32), String> {
-    // get screen size and set draw color
+    // get screen size
     let screen_size: (u32, u32) = canvas.output_size().unwrap();
-    canvas.set_draw_color(Color::BLACK);
     
     // calculate number of cells from cell size and available screen size
     // first, ensure screen size is large enough
@@ -117,6 +338,44 @@ pub fn render_grid(
     let rows: i32 = min(max_rows, available_height / cell_size);
     let cols: i32 = min(max_cols, available_width / cell_size);
 
+    Ok((rows, cols))
+}
+
+/// Given the canvas context, render the grid of the given dimensions with lines of the given color
+/// Lines are skipped entirely if the color is fully transparent
+/// On a hexagonal grid the lines between columns are broken into a shifted piece for each row
+pub fn render_grid(
+    canvas: &mut Canvas<Window>,
+    color: Color,
+    cell_size: i32,
+    grid_dim: (i32, i32),
+    is_hex: bool,
+) {
+    if color.a == 0 {
+        return;
+    }
+    canvas.set_draw_color(color);
+    let (rows, cols) = grid_dim;
+
+    if is_hex {
+        canvas.set_clip_rect(grid_area(cell_size, grid_dim));
+        for i in 0..=rows {
+            let start: Vector2 = Vector2::new(0, i).to_screen(cell_size);
+            canvas.draw_line(Point::new(start.x, start.y), Point::new(start.x + cols * cell_size, start.y)).unwrap();
+        }
+        // one extra column on each side fills in the cells shifted into view
+        for i in 0..rows {
+            let shift: i32 = hex_shift(i, rows, cell_size);
+            for j in -1..=cols + 1 {
+                let top: Vector2 = Vector2::new(j, i).to_screen(cell_size);
+                let x: i32 = top.x + shift.rem_euclid(cell_size);
+                canvas.draw_line(Point::new(x, top.y), Point::new(x, top.y + cell_size)).unwrap();
+            }
+        }
+        canvas.set_clip_rect(None);
+        return;
+    }
+
     // draw the lines for the rows
     for i in 0..=rows {
         let start_point: Vector2 = Cell::from_top_left(Vector2::new(0, i as i32)
@@ -138,7 +397,137 @@ pub fn render_grid(
             .bottom_left;
         canvas.draw_line(start_point, end_point).unwrap();
     }
+}
 
-    Ok((rows, cols))
+/// Render heavier lines every few cells of the backend grid, to help with counting cells
+/// The grid on screen has the given dimensions and starts at the given (x, y) backend cell
+/// On a hexagonal grid the lines between columns are broken into a shifted piece for each row
+pub fn render_major_lines(
+    canvas: &mut Canvas<Window>,
+    color: Color,
+    cell_size: i32,
+    grid_dim: (i32, i32),
+    cells_start: (usize, usize),
+    is_hex: bool,
+) {
+    canvas.set_draw_color(color);
+    let top_left: Vector2 = Vector2::new(0, 0).to_screen(cell_size);
+    let offset: i32 = MAJOR_LINE_WIDTH as i32 / 2;
+    if is_hex {
+        canvas.set_clip_rect(grid_area(cell_size, grid_dim));
+    }
+
+    // draw the lines for the rows
+    for i in 0..=grid_dim.0 {
+        if (cells_start.1 + i as usize).is_multiple_of(MAJOR_LINE_SPACING) {
+            let start: Vector2 = Vector2::new(0, i).to_screen(cell_size);
+            let line = Rect::new(start.x - offset,
+                start.y - offset,
+                (grid_dim.1 * cell_size) as u32 + MAJOR_LINE_WIDTH,
+                MAJOR_LINE_WIDTH);
+            canvas.fill_rect(line).unwrap();
+        }
+    }
+
+    // draw the lines for the columns
+    for j in 0..=grid_dim.1 {
+        if (cells_start.0 + j as usize).is_multiple_of(MAJOR_LINE_SPACING) {
+            let start: Vector2 = Vector2::new(j, 0).to_screen(cell_size);
+            if is_hex {
+                for i in 0..grid_dim.0 {
+                    let line = Rect::new(start.x + hex_shift(i, grid_dim.0, cell_size) - offset,
+                        start.y + i * cell_size,
+                        MAJOR_LINE_WIDTH,
+                        cell_size as u32);
+                    canvas.fill_rect(line).unwrap();
+                }
+                continue;
+            }
+            let line = Rect::new(start.x - offset,
+                top_left.y - offset,
+                MAJOR_LINE_WIDTH,
+                (grid_dim.0 * cell_size) as u32 + MAJOR_LINE_WIDTH);
+            canvas.fill_rect(line).unwrap();
+        }
+    }
+    canvas.set_clip_rect(None);
+}
+
+/// Render a translucent box with an outline over the part of a region of backend cells on screen
+/// The grid on screen has the given dimensions and starts at the given (x, y) backend cell
+/// On a hexagonal grid each row of the region is filled on its own, shifted sideways
+pub fn render_selection(
+    canvas: &mut Canvas<Window>,
+    color: Color,
+    cell_size: i32,
+    grid_dim: (i32, i32),
+    cells_start: (usize, usize),
+    region: Region,
+    is_hex: bool,
+) {
+    // clip the region to the cells on screen, in screen grid coordinates
+    let left: i32 = (region.col as i32 - cells_start.0 as i32).max(0);
+    let top: i32 = (region.row as i32 - cells_start.1 as i32).max(0);
+    let right: i32 = ((region.col + region.cols) as i32 - cells_start.0 as i32).min(grid_dim.1);
+    let bottom: i32 = ((region.row + region.rows) as i32 - cells_start.1 as i32).min(grid_dim.0);
+    if left >= right || top >= bottom {
+        return;
+    }
+
+    if is_hex {
+        canvas.set_clip_rect(grid_area(cell_size, grid_dim));
+        canvas.set_draw_color(Color::RGBA(color.r, color.g, color.b, SELECTION_ALPHA));
+        for i in top..bottom {
+            let start: Vector2 = Vector2::new(left, i).to_screen(cell_size);
+            let rect = Rect::new(start.x + hex_shift(i, grid_dim.0, cell_size),
+                start.y,
+                ((right - left) * cell_size) as u32,
+                cell_size as u32);
+            canvas.fill_rect(rect).unwrap();
+        }
+        canvas.set_clip_rect(None);
+        return;
+    }
+
+    let start: Vector2 = Vector2::new(left, top).to_screen(cell_size);
+    let rect = Rect::new(start.x,
+        start.y,
+        ((right - left) * cell_size) as u32,
+        ((bottom - top) * cell_size) as u32);
+    canvas.set_draw_color(Color::RGBA(color.r, color.g, color.b, SELECTION_ALPHA));
+    canvas.fill_rect(rect).unwrap();
+    canvas.set_draw_color(color);
+    canvas.draw_rect(rect).unwrap();
 }
 
+/// Render a marker over each ant on screen, a square in the middle of its cell with a smaller
+/// square for its head on the side it is facing
+pub fn render_ants(
+    canvas: &mut Canvas<Window>,
+    color: Color,
+    cell_size: i32,
+    grid_dim: (i32, i32),
+    cells_start: (usize, usize),
+    ants: &[Ant],
+) {
+    canvas.set_draw_color(color);
+    let inset: i32 = cell_size / 4;
+    for ant in ants {
+        let col: i32 = ant.col as i32 - cells_start.0 as i32;
+        let row: i32 = ant.row as i32 - cells_start.1 as i32;
+        if row < 0 || col < 0 || row >= grid_dim.0 || col >= grid_dim.1 {
+            continue;
+        }
+
+        let start: Vector2 = Vector2::new(col, row).to_screen(cell_size);
+        let body: u32 = (cell_size - 2 * inset).max(1) as u32;
+        canvas.fill_rect(Rect::new(start.x + inset, start.y + inset, body, body)).unwrap();
+        if inset > 0 {
+            let (di, dj) = ant.direction.offset();
+            let reach: i32 = cell_size / 2 - inset / 2;
+            let head_x: i32 = start.x + cell_size / 2 + dj as i32 * reach - inset / 2;
+            let head_y: i32 = start.y + cell_size / 2 + di as i32 * reach - inset / 2;
+            canvas.fill_rect(Rect::new(head_x, head_y, inset as u32, inset as u32)).unwrap();
+        }
+    }
+}
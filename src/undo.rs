@@ -0,0 +1,106 @@
+use crate::life::Grid;
+
+use std::mem;
+
+// most grids to remember for undoing
+const UNDO_LIMIT: usize = 100;
+
+/// Stacks of earlier and later grids, for undoing and redoing changes to the grid
+#[derive(Default)]
+pub struct UndoStack {
+    undo: Vec<Grid>,
+    redo: Vec<Grid>,
+}
+
+impl UndoStack {
+    /// Remember the grid before it is changed, forgetting anything that could be redone
+    pub fn push(&mut self, cells: &[Vec<u8>]) {
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.undo.push(cells.to_vec());
+        self.redo.clear();
+    }
+
+    /// Replace the grid with the last remembered one, returning whether there was one
+    pub fn undo(&mut self, cells: &mut Grid) -> bool {
+        match self.undo.pop() {
+            Some(previous) => {
+                self.redo.push(mem::replace(cells, previous));
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Replace the grid with the last undone one, returning whether there was one
+    pub fn redo(&mut self, cells: &mut Grid) -> bool {
+        match self.redo.pop() {
+            Some(next) => {
+                self.undo.push(mem::replace(cells, next));
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Make a one-cell grid holding the given state
+    fn grid(state: u8) -> Grid {
+        vec![vec![state]]
+    }
+
+    #[test]
+    fn undo_and_redo_swap_grids() {
+        let mut stack = UndoStack::default();
+        let mut cells: Grid = grid(1);
+        assert!(!stack.undo(&mut cells));
+
+        stack.push(&cells);
+        cells = grid(2);
+        stack.push(&cells);
+        cells = grid(3);
+
+        assert!(stack.undo(&mut cells));
+        assert_eq!(cells, grid(2));
+        assert!(stack.undo(&mut cells));
+        assert_eq!(cells, grid(1));
+        assert!(!stack.undo(&mut cells));
+
+        assert!(stack.redo(&mut cells));
+        assert!(stack.redo(&mut cells));
+        assert_eq!(cells, grid(3));
+        assert!(!stack.redo(&mut cells));
+    }
+
+    #[test]
+    fn pushing_forgets_redos() {
+        let mut stack = UndoStack::default();
+        let mut cells: Grid = grid(1);
+        stack.push(&cells);
+        cells = grid(2);
+        assert!(stack.undo(&mut cells));
+
+        stack.push(&cells);
+        assert!(!stack.redo(&mut cells));
+        assert_eq!(cells, grid(1));
+    }
+
+    #[test]
+    fn only_the_latest_grids_are_kept() {
+        let mut stack = UndoStack::default();
+        let mut cells: Grid = grid(0);
+        for state in 0..=UNDO_LIMIT as u8 {
+            stack.push(&grid(state));
+        }
+        for _ in 0..UNDO_LIMIT {
+            assert!(stack.undo(&mut cells));
+        }
+        assert!(!stack.undo(&mut cells));
+        assert_eq!(cells, grid(1));
+    }
+}
@@ -0,0 +1,386 @@
+use crate::draw::fill_triangle;
+use crate::text::TextCache;
+use crate::theme::{mix, Theme};
+use crate::ui::Vector2;
+
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::Window;
+
+// space between the text of a button and its border
+const TEXT_PADDING: i32 = 10;
+
+// space between the border of the slider and the bar inside it
+const SLIDER_PADDING: i32 = 2;
+
+// space between the text of a tooltip and its border, and between a tooltip and its widget
+const TOOLTIP_PADDING: i32 = 6;
+const TOOLTIP_OFFSET: i32 = 4;
+
+// size of the play and pause shapes, relative to the height of their button
+const PLAY_WIDTH_RATIO: f32 = 0.866;
+const PAUSE_BAR_RATIO: f32 = 0.25;
+const PAUSE_GAP_RATIO: f32 = 0.33;
+
+/// Whether the mouse is over a widget, and whether it is being held down on it
+#[derive(Copy, Clone, Default, Eq, PartialEq)]
+pub struct WidgetState {
+    pub is_hovered: bool,
+    pub is_pressed: bool,
+}
+
+/// Placement, state and tooltip shared by every widget
+pub struct WidgetBase {
+    rect: Rect,
+    state: WidgetState,
+    tooltip: Option<&'static str>,
+}
+
+impl WidgetBase {
+    fn new(tooltip: Option<&'static str>) -> Self {
+        WidgetBase { rect: Rect::new(0, 0, 1, 1), state: WidgetState::default(), tooltip }
+    }
+}
+
+/// A control drawn in the buffers around the grid
+/// Widgets are laid out by asking for their size and then placing them, after which they can be
+/// rendered and hit-tested against the mouse
+pub trait Widget {
+    fn base(&self) -> &WidgetBase;
+    fn base_mut(&mut self) -> &mut WidgetBase;
+
+    /// Get the size the widget would like to take up, as (width, height)
+    fn size(&self, text_cache: &TextCache) -> (i32, i32);
+
+    /// Render the widget within the rectangle it was placed in
+    fn render(&mut self, canvas: &mut Canvas<Window>, theme: &Theme, text_cache: &mut TextCache);
+
+    /// Whether the widget reacts to the mouse, as opposed to only showing information
+    fn is_interactive(&self) -> bool {
+        true
+    }
+
+    /// Place the widget in the given rectangle on screen
+    fn place(&mut self, rect: Rect) {
+        self.base_mut().rect = rect;
+    }
+
+    /// Get the rectangle the widget was last placed in
+    fn rect(&self) -> Rect {
+        self.base().rect
+    }
+
+    /// Check whether the given screen coordinates are on the widget
+    fn contains(&self, x: i32, y: i32) -> bool {
+        self.is_interactive() && self.rect().contains_point(Point::new(x, y))
+    }
+
+    fn state(&self) -> WidgetState {
+        self.base().state
+    }
+
+    fn set_state(&mut self, state: WidgetState) {
+        self.base_mut().state = state;
+    }
+
+    /// Get the text describing what the widget does, if it has any
+    fn tooltip(&self) -> Option<&'static str> {
+        self.base().tooltip
+    }
+}
+
+/// Render text centered within a rectangle
+fn render_centered_text(
+    canvas: &mut Canvas<Window>,
+    text_cache: &mut TextCache,
+    text: &str,
+    color: Color,
+    rect: Rect,
+) {
+    if text.is_empty() {
+        return;
+    }
+    let dimensions: (i32, i32) = text_cache.get_dimensions(text);
+    let text_texture: &Texture = text_cache.render_text(text, color);
+    let text_rect = Rect::new(rect.x() + (rect.width() as i32 - dimensions.0) / 2,
+        rect.y() + (rect.height() as i32 - dimensions.1) / 2,
+        dimensions.0 as u32,
+        dimensions.1 as u32);
+    canvas.copy(text_texture, None, text_rect).unwrap();
+}
+
+/// Fill the background of a widget to show that the mouse is over it or holding it down
+fn render_feedback(canvas: &mut Canvas<Window>, theme: &Theme, rect: Rect, state: WidgetState) {
+    let color: Color = if state.is_pressed {
+        theme.control_fill
+    } else if state.is_hovered {
+        mix(theme.background, theme.control_fill, 0.5)
+    } else {
+        return;
+    };
+    canvas.set_draw_color(color);
+    canvas.fill_rect(rect).unwrap();
+}
+
+/// Render a tooltip with the given text next to a widget, below it if the widget is in the top
+/// half of the screen and above it otherwise, keeping the tooltip on screen
+pub fn render_tooltip(
+    canvas: &mut Canvas<Window>,
+    theme: &Theme,
+    text_cache: &mut TextCache,
+    text: &str,
+    widget_rect: Rect,
+) {
+    let screen_size: (u32, u32) = canvas.output_size().unwrap();
+    let screen_width = screen_size.0 as i32;
+    let screen_height = screen_size.1 as i32;
+
+    // size the box to the text and line it up with the widget
+    let dimensions: (i32, i32) = text_cache.get_dimensions(text);
+    let width: i32 = dimensions.0 + TOOLTIP_PADDING * 2;
+    let height: i32 = dimensions.1 + TOOLTIP_PADDING * 2;
+    let x: i32 = widget_rect.x().clamp(0, (screen_width - width).max(0));
+    let y: i32 = if widget_rect.center().y() < screen_height / 2 {
+        widget_rect.bottom() + TOOLTIP_OFFSET
+    } else {
+        widget_rect.top() - TOOLTIP_OFFSET - height
+    };
+    let rect = Rect::new(x, y, width as u32, height as u32);
+
+    canvas.set_draw_color(theme.background);
+    canvas.fill_rect(rect).unwrap();
+    canvas.set_draw_color(theme.control);
+    canvas.draw_rect(rect).unwrap();
+    render_centered_text(canvas, text_cache, text, theme.text, rect);
+}
+
+/// Button showing a line of text inside a border
+pub struct Button {
+    base: WidgetBase,
+    pub text: String,
+    height: i32,
+}
+
+impl Button {
+    pub fn new(height: i32, tooltip: &'static str) -> Self {
+        Button { base: WidgetBase::new(Some(tooltip)), text: String::new(), height }
+    }
+}
+
+impl Widget for Button {
+    fn base(&self) -> &WidgetBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut WidgetBase {
+        &mut self.base
+    }
+
+    fn size(&self, text_cache: &TextCache) -> (i32, i32) {
+        (text_cache.get_dimensions(&self.text).0 + TEXT_PADDING * 2, self.height)
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, theme: &Theme, text_cache: &mut TextCache) {
+        render_feedback(canvas, theme, self.base.rect, self.base.state);
+        canvas.set_draw_color(theme.control);
+        canvas.draw_rect(self.base.rect).unwrap();
+        render_centered_text(canvas, text_cache, &self.text, theme.text, self.base.rect);
+    }
+}
+
+/// Button that switches between two states, showing different text for each
+pub struct Toggle {
+    base: WidgetBase,
+    pub is_on: bool,
+    on_text: &'static str,
+    off_text: &'static str,
+    height: i32,
+}
+
+impl Toggle {
+    pub fn new(on_text: &'static str, off_text: &'static str, height: i32, tooltip: &'static str) -> Self {
+        Toggle { base: WidgetBase::new(Some(tooltip)), is_on: false, on_text, off_text, height }
+    }
+
+    fn text(&self) -> &'static str {
+        if self.is_on { self.on_text } else { self.off_text }
+    }
+}
+
+impl Widget for Toggle {
+    fn base(&self) -> &WidgetBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut WidgetBase {
+        &mut self.base
+    }
+
+    fn size(&self, text_cache: &TextCache) -> (i32, i32) {
+        (text_cache.get_dimensions(self.text()).0 + TEXT_PADDING * 2, self.height)
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, theme: &Theme, text_cache: &mut TextCache) {
+        render_feedback(canvas, theme, self.base.rect, self.base.state);
+        canvas.set_draw_color(theme.control);
+        canvas.draw_rect(self.base.rect).unwrap();
+        render_centered_text(canvas, text_cache, self.text(), theme.text, self.base.rect);
+    }
+}
+
+/// Horizontal bar that is filled to show a value from 0 to 1, with text on top
+pub struct Slider {
+    base: WidgetBase,
+    pub value: f32,
+    pub text: String,
+    width: i32,
+    height: i32,
+}
+
+impl Slider {
+    pub fn new(width: i32, height: i32, tooltip: &'static str) -> Self {
+        Slider { base: WidgetBase::new(Some(tooltip)), value: 0.0, text: String::new(), width, height }
+    }
+
+    /// Get the value of the slider if it were dragged to the given x coordinate
+    pub fn value_at(&self, x: i32) -> f32 {
+        ((x - self.base.rect.x()) as f32 / self.base.rect.width() as f32).clamp(0.0, 1.0)
+    }
+}
+
+impl Widget for Slider {
+    fn base(&self) -> &WidgetBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut WidgetBase {
+        &mut self.base
+    }
+
+    fn size(&self, _text_cache: &TextCache) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, theme: &Theme, text_cache: &mut TextCache) {
+        let outer_rect: Rect = self.base.rect;
+        let inner_rect = Rect::new(outer_rect.x() + SLIDER_PADDING,
+            outer_rect.y() + SLIDER_PADDING,
+            ((outer_rect.width() as i32 - 2 * SLIDER_PADDING) as f32 * self.value) as u32,
+            (outer_rect.height() as i32 - 2 * SLIDER_PADDING) as u32);
+
+        // the bar is drawn in the pressed color, so only show hovering behind it
+        let state = WidgetState { is_pressed: false, ..self.base.state };
+        render_feedback(canvas, theme, outer_rect, state);
+        canvas.set_draw_color(theme.control);
+        canvas.draw_rect(outer_rect).unwrap();
+        canvas.set_draw_color(theme.control_fill);
+        canvas.fill_rect(inner_rect).unwrap();
+        render_centered_text(canvas, text_cache, &self.text, theme.text, outer_rect);
+    }
+}
+
+/// Line of text that only shows information
+pub struct Label {
+    base: WidgetBase,
+    pub text: String,
+}
+
+impl Default for Label {
+    fn default() -> Self {
+        Label { base: WidgetBase::new(None), text: String::new() }
+    }
+}
+
+impl Widget for Label {
+    fn base(&self) -> &WidgetBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut WidgetBase {
+        &mut self.base
+    }
+
+    fn size(&self, text_cache: &TextCache) -> (i32, i32) {
+        text_cache.get_dimensions(&self.text)
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, theme: &Theme, text_cache: &mut TextCache) {
+        render_centered_text(canvas, text_cache, &self.text, theme.text, self.base.rect);
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+/// Picture shown on an icon button, either an image or a shape drawn directly
+pub enum Icon<'a> {
+    Image(Texture<'a>),
+    Play,
+    Pause,
+}
+
+/// Square button showing an icon without a border
+pub struct IconButton<'a> {
+    base: WidgetBase,
+    pub icon: Icon<'a>,
+    size: i32,
+}
+
+impl<'a> IconButton<'a> {
+    pub fn new(icon: Icon<'a>, size: i32, tooltip: &'static str) -> Self {
+        IconButton { base: WidgetBase::new(Some(tooltip)), icon, size }
+    }
+}
+
+impl Widget for IconButton<'_> {
+    fn base(&self) -> &WidgetBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut WidgetBase {
+        &mut self.base
+    }
+
+    fn size(&self, _text_cache: &TextCache) -> (i32, i32) {
+        (self.size, self.size)
+    }
+
+    fn render(&mut self, canvas: &mut Canvas<Window>, theme: &Theme, _text_cache: &mut TextCache) {
+        let rect: Rect = self.base.rect;
+        let center: Point = rect.center();
+        let height: i32 = rect.height() as i32;
+        render_feedback(canvas, theme, rect, self.base.state);
+        canvas.set_draw_color(theme.control);
+
+        match &mut self.icon {
+            Icon::Image(texture) => {
+                // tint the white mask of the icon to match the theme
+                texture.set_color_mod(theme.control.r, theme.control.g, theme.control.b);
+                canvas.copy(texture, None, rect).unwrap();
+            },
+            Icon::Play => {
+                let half_width: i32 = (height as f32 * PLAY_WIDTH_RATIO) as i32 / 2;
+                let a = Vector2::new(center.x() - half_width, rect.top());
+                let b = Vector2::new(center.x() - half_width, rect.bottom());
+                let c = Vector2::new(center.x() + half_width, center.y());
+                fill_triangle(canvas, a, b, c);
+            },
+            Icon::Pause => {
+                let bar_width: i32 = (height as f32 * PAUSE_BAR_RATIO) as i32;
+                let gap: i32 = (height as f32 * PAUSE_GAP_RATIO) as i32;
+                let left_rect = Rect::new(center.x() - gap / 2 - bar_width,
+                    rect.y(),
+                    bar_width as u32,
+                    height as u32);
+                let right_rect = Rect::new(center.x() + gap / 2,
+                    rect.y(),
+                    bar_width as u32,
+                    height as u32);
+                canvas.fill_rect(left_rect).unwrap();
+                canvas.fill_rect(right_rect).unwrap();
+            },
+        }
+    }
+}